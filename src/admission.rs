@@ -0,0 +1,521 @@
+//! Admission of registered people to modules
+
+use crate::{
+    input::{
+        invalid_data, load_email_list, CapacityRecord, QuotaRecord, StateAliasRecord, StateMeaning,
+    },
+    registrations::{normalize_email, ModuleId, PersonId, Registrations},
+};
+use log::{debug, warn};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+};
+
+/// Constraints on how many people can attend each module
+#[derive(Debug, Default)]
+pub struct Limits {
+    /// Maximal number of attendees for each module
+    pub capacities: HashMap<ModuleId, Capacity>,
+
+    /// Maximal number of attendees for each (module, simplified affiliation)
+    pub affiliation_quotas: HashMap<(ModuleId, Box<str>), usize>,
+
+    /// Registration state of people who should go straight to waiting lists
+    ///
+    /// Matching is case-insensitive, and these people don't take up seats.
+    pub waitlist_state: Option<Box<str>>,
+
+    /// Maximal number of oversubscribed modules that someone gets a seat in
+    /// before people with fewer such seats, see [`Limits::admit_all()`]
+    pub fair_rotation: Option<usize>,
+
+    /// Favor people who ranked a module higher in their choice of modules,
+    /// see [`Limits::admit_all()`]
+    pub respect_choice_order: bool,
+
+    /// Meaning of registration states, by lowercase state
+    ///
+    /// This takes precedence over `waitlist_state`.
+    pub state_aliases: HashMap<Box<str>, StateMeaning>,
+
+    /// Normalized emails of people who are guaranteed a seat
+    ///
+    /// These people come first in every module they chose, and are accepted
+    /// even if this exceeds the capacity or their affiliation's quota, unless
+    /// their registration was cancelled.
+    pub vips: HashSet<Box<str>>,
+}
+//
+impl Limits {
+    /// Load the capacity and quota files, if any
+    pub fn load(
+        capacities_path: Option<&str>,
+        quotas_path: Option<&str>,
+        registrations: &Registrations,
+    ) -> csv::Result<Self> {
+        let mut result = Self::default();
+        if let Some(path) = capacities_path {
+            debug!("Loading module capacities...");
+            for record in csv::Reader::from_path(path)?.deserialize() {
+                let CapacityRecord { module, capacity } = record?;
+                debug!("- {module}: {capacity}");
+                let capacity = Capacity::parse(&capacity)?;
+                if let Some(module_id) = registrations.find_module(&module, "capacity") {
+                    result.capacities.insert(module_id, capacity);
+                }
+            }
+        }
+        if let Some(path) = quotas_path {
+            debug!("Loading per-affiliation quotas...");
+            for record in csv::Reader::from_path(path)?.deserialize() {
+                let QuotaRecord {
+                    module,
+                    affiliation,
+                    quota,
+                } = record?;
+                debug!("- {module} / {affiliation}: {quota}");
+                if let Some(module_id) = registrations.find_module(&module, "quota") {
+                    result
+                        .affiliation_quotas
+                        .insert((module_id, affiliation), quota);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Set the capacity of some modules, overriding the capacities file
+    ///
+    /// Overrides should come from [`Capacity::parse_override()`].
+    pub fn override_capacities(
+        &mut self,
+        overrides: &[(Box<str>, Capacity)],
+        registrations: &Registrations,
+    ) {
+        for (module, capacity) in overrides {
+            if let Some(module_id) = registrations.find_module(module, "capacity") {
+                debug!("- {module}: {capacity} (from the command line)");
+                self.capacities.insert(module_id, *capacity);
+            }
+        }
+    }
+
+    /// Load a list of people who are guaranteed a seat, see [`Limits::vips`]
+    ///
+    /// This is a text file with one email address per line.
+    pub fn load_vips(&mut self, path: &str) -> std::io::Result<()> {
+        debug!("Loading VIP list...");
+        self.vips = load_email_list(path)?;
+        Ok(())
+    }
+
+    /// Truth that someone is guaranteed a seat, see [`Limits::vips`]
+    pub fn is_vip(&self, registrations: &Registrations, person_id: PersonId) -> bool {
+        !self.vips.is_empty()
+            && (self.vips).contains(&normalize_email(
+                &registrations.persons[person_id].identity.email_address,
+            ))
+    }
+
+    /// Load a CSV file of registration state aliases
+    ///
+    /// This file should have "State" and "Meaning" columns, where the
+    /// meaning is one of "active", "waitlist" or "cancelled".
+    pub fn load_state_aliases(&mut self, path: &str) -> csv::Result<()> {
+        debug!("Loading registration state aliases...");
+        for record in csv::Reader::from_path(path)?.deserialize() {
+            let StateAliasRecord { state, meaning } = record?;
+            debug!("- {state}: {meaning:?}");
+            self.state_aliases
+                .insert(state.trim().to_lowercase().into(), meaning);
+        }
+        Ok(())
+    }
+
+    /// Warn about registration states that no state alias mentions
+    ///
+    /// This only makes sense when state aliases are used. People with such a
+    /// state are admitted as usual, unless it matches `waitlist_state`.
+    pub fn warn_about_unrecognized_states(&self, registrations: &Registrations) {
+        let mut counts = BTreeMap::<Box<str>, (&str, usize)>::new();
+        for person in &registrations.persons {
+            let Some(state) = person.registration_state.as_deref() else {
+                continue;
+            };
+            let is_waitlist_state = self
+                .waitlist_state
+                .as_deref()
+                .is_some_and(|waitlist_state| {
+                    state.trim().eq_ignore_ascii_case(waitlist_state.trim())
+                });
+            let key = Box::<str>::from(state.trim().to_lowercase());
+            if !is_waitlist_state && !self.state_aliases.contains_key(&key) {
+                counts.entry(key).or_insert((state.trim(), 0)).1 += 1;
+            }
+        }
+        if !counts.is_empty() {
+            let states = counts
+                .into_values()
+                .map(|(state, count)| format!("\"{state}\" ({count})"))
+                .collect::<Vec<_>>();
+            warn!(
+                "Some registration states have no alias, these people are treated as active: {}",
+                states.join(", ")
+            );
+        }
+    }
+
+    /// Admit people to every module, given the output of
+    /// [`Registrations::module_rosters()`]
+    ///
+    /// Without fair rotation, this is [`Limits::admit()`] applied to each
+    /// module. With a fair rotation cap of N, oversubscribed modules (which
+    /// have more registrations than seats) are processed in start time order,
+    /// and people who already got a seat in N oversubscribed modules are
+    /// moved to the end of the registration order of the next ones. This lets
+    /// later registrants get a seat instead of them, while still giving them
+    /// any seat that nobody else wants.
+    ///
+    /// When respecting choice order, the order in which people listed their
+    /// modules is taken as a preference order. Each oversubscribed module
+    /// then admits people who ranked it first, then people who ranked it
+    /// second, and so on, in registration order within each rank. Other
+    /// modules keep the registration order. This is done before fair rotation
+    /// is applied.
+    pub fn admit_all(
+        &self,
+        registrations: &Registrations,
+        mut module_rosters: Vec<Vec<PersonId>>,
+    ) -> Vec<Roster> {
+        let mut rosters = (0..module_rosters.len())
+            .map(|_| Roster::default())
+            .collect::<Vec<_>>();
+        let mut num_oversubscribed_seats = vec![0; registrations.persons.len()];
+        for module_id in registrations.modules_by_start_time() {
+            let mut ordered_persons = std::mem::take(&mut module_rosters[module_id]);
+            let is_oversubscribed = self
+                .num_seats(registrations, module_id, &ordered_persons)
+                .is_some_and(|num_seats| {
+                    self.num_active(registrations, &ordered_persons) > num_seats
+                });
+            if self.respect_choice_order && is_oversubscribed {
+                ordered_persons.sort_by_key(|&person_id| {
+                    registrations.persons[person_id]
+                        .choice_of_modules
+                        .iter()
+                        .position(|&choice| choice == module_id)
+                });
+            }
+            if let (Some(cap), true) = (self.fair_rotation, is_oversubscribed) {
+                let (below_cap, at_cap): (Vec<_>, Vec<_>) = ordered_persons
+                    .into_iter()
+                    .partition(|&person_id| num_oversubscribed_seats[person_id] < cap);
+                ordered_persons = below_cap.into_iter().chain(at_cap).collect();
+            }
+            let roster = self.admit(registrations, module_id, ordered_persons);
+            if is_oversubscribed {
+                for &person_id in &roster.accepted {
+                    num_oversubscribed_seats[person_id] += 1;
+                }
+            }
+            rosters[module_id] = roster;
+        }
+        rosters
+    }
+
+    /// Admit people to a module in registration order, within the limits
+    ///
+    /// VIPs are moved to the front of the registration order, and always
+    /// accepted unless their registration was cancelled.
+    pub fn admit(
+        &self,
+        registrations: &Registrations,
+        module_id: ModuleId,
+        ordered_persons: Vec<PersonId>,
+    ) -> Roster {
+        let (vips, others): (Vec<_>, Vec<_>) = ordered_persons
+            .into_iter()
+            .partition(|&person_id| self.is_vip(registrations, person_id));
+        let num_vips = vips.len();
+        let ordered_persons = vips.into_iter().chain(others).collect::<Vec<_>>();
+        let capacity = self.num_seats(registrations, module_id, &ordered_persons);
+        let mut affiliation_counts = HashMap::<&str, usize>::new();
+        let mut roster = Roster {
+            num_seats: capacity,
+            ..Roster::default()
+        };
+        for (rank, person_id) in ordered_persons.into_iter().enumerate() {
+            let meaning = self.state_meaning(registrations, person_id);
+            if meaning == StateMeaning::Cancelled {
+                continue;
+            }
+            if rank < num_vips {
+                debug!(
+                    "- {} is a VIP of module {}",
+                    registrations.persons[person_id].identity,
+                    registrations.modules[module_id].name
+                );
+                let affiliation = registrations.persons[person_id]
+                    .identity
+                    .simplified_affiliation();
+                *affiliation_counts.entry(affiliation).or_default() += 1;
+                roster.accepted.push(person_id);
+                continue;
+            }
+            if meaning == StateMeaning::Waitlist {
+                roster
+                    .waitlisted
+                    .push((person_id, WaitlistReason::RegistrationState));
+                continue;
+            }
+            if capacity.is_some_and(|capacity| roster.accepted.len() >= capacity) {
+                roster
+                    .waitlisted
+                    .push((person_id, WaitlistReason::Capacity));
+                continue;
+            }
+            let affiliation = registrations.persons[person_id]
+                .identity
+                .simplified_affiliation();
+            let count = affiliation_counts.entry(affiliation).or_default();
+            let quota = self
+                .affiliation_quotas
+                .get(&(module_id, affiliation.into()))
+                .copied();
+            if quota.is_some_and(|quota| *count >= quota) {
+                debug!(
+                    "- {} is over their affiliation's quota for module {}",
+                    registrations.persons[person_id].identity,
+                    registrations.modules[module_id].name
+                );
+                roster
+                    .waitlisted
+                    .push((person_id, WaitlistReason::AffiliationQuota));
+                continue;
+            }
+            *count += 1;
+            roster.accepted.push(person_id);
+        }
+        roster
+    }
+
+    /// Number of seats of a module, if it has a capacity, given the people
+    /// who registered to it
+    ///
+    /// Percentage capacities are computed from the people whose registration
+    /// was not cancelled, as cancelled people are not listed in the roster.
+    pub fn num_seats(
+        &self,
+        registrations: &Registrations,
+        module_id: ModuleId,
+        persons: &[PersonId],
+    ) -> Option<usize> {
+        let capacity = self.capacities.get(&module_id)?;
+        Some(capacity.num_seats(self.num_active(registrations, persons)))
+    }
+
+    /// Number of people whose registration was not cancelled
+    fn num_active(&self, registrations: &Registrations, persons: &[PersonId]) -> usize {
+        persons
+            .iter()
+            .filter(|&&person_id| {
+                self.state_meaning(registrations, person_id) != StateMeaning::Cancelled
+            })
+            .count()
+    }
+
+    /// Truth that someone's registration state puts them on waiting lists
+    pub fn is_waitlisted_in_indico(
+        &self,
+        registrations: &Registrations,
+        person_id: PersonId,
+    ) -> bool {
+        self.state_meaning(registrations, person_id) == StateMeaning::Waitlist
+    }
+
+    /// Meaning of someone's registration state
+    ///
+    /// State aliases are looked up first, then states that match
+    /// `waitlist_state` mean waitlisting. Anything else is active.
+    pub fn state_meaning(
+        &self,
+        registrations: &Registrations,
+        person_id: PersonId,
+    ) -> StateMeaning {
+        let Some(state) = registrations.persons[person_id]
+            .registration_state
+            .as_deref()
+        else {
+            return StateMeaning::Active;
+        };
+        if let Some(&meaning) = self.state_aliases.get(&*state.trim().to_lowercase()) {
+            return meaning;
+        }
+        match &self.waitlist_state {
+            Some(waitlist_state) if state.trim().eq_ignore_ascii_case(waitlist_state.trim()) => {
+                StateMeaning::Waitlist
+            }
+            _ => StateMeaning::Active,
+        }
+    }
+}
+
+/// Maximal number of attendees of a module
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Capacity {
+    /// Fixed number of seats
+    Seats(usize),
+
+    /// Percentage of the people who registered to the module
+    Percentage(f64),
+}
+//
+impl Capacity {
+    /// Parse a capacity, either a number of seats or a percentage like "50%"
+    pub fn parse(capacity: &str) -> csv::Result<Self> {
+        let capacity = capacity.trim();
+        let invalid =
+            |e: &dyn Display| invalid_data(format!("Invalid capacity \"{capacity}\" ({e})"));
+        if let Some(percentage) = capacity.strip_suffix('%') {
+            let percentage = percentage.trim().parse::<f64>().map_err(|e| invalid(&e))?;
+            if !(percentage.is_finite() && percentage > 0.0) {
+                return Err(invalid(&"percentage should be positive"));
+            }
+            Ok(Self::Percentage(percentage))
+        } else {
+            capacity.parse().map(Self::Seats).map_err(|e| invalid(&e))
+        }
+    }
+
+    /// Parse a capacity override of the form "MODULE=CAPACITY"
+    ///
+    /// The split happens at the last `=`, so module names may contain `=`.
+    pub fn parse_override(spec: &str) -> Result<(Box<str>, Self), String> {
+        let (module, capacity) = spec
+            .rsplit_once('=')
+            .ok_or_else(|| format!("expected MODULE=CAPACITY, found \"{spec}\""))?;
+        let module = module.trim();
+        if module.is_empty() {
+            return Err(format!("no module name in \"{spec}\""));
+        }
+        let capacity = Self::parse(capacity).map_err(|e| e.to_string())?;
+        Ok((module.into(), capacity))
+    }
+
+    /// Number of seats, given how many people registered to the module
+    ///
+    /// Percentages are rounded down, but always leave at least one seat.
+    pub fn num_seats(self, num_registered: usize) -> usize {
+        match self {
+            Self::Seats(seats) => seats,
+            Self::Percentage(percentage) => {
+                ((num_registered as f64 * percentage / 100.0).floor() as usize).max(1)
+            }
+        }
+    }
+}
+//
+impl Display for Capacity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Seats(seats) => write!(f, "{seats}"),
+            Self::Percentage(percentage) => write!(f, "{percentage}%"),
+        }
+    }
+}
+
+/// Who got a seat in a module, and who didn't
+#[derive(Debug, Default)]
+pub struct Roster {
+    /// People who were accepted, in registration order
+    pub accepted: Vec<PersonId>,
+
+    /// People who were put on the waiting list, in registration order
+    pub waitlisted: Vec<(PersonId, WaitlistReason)>,
+
+    /// Number of seats that admission enforced, if the module has a capacity
+    ///
+    /// See [`Limits::num_seats()`] for details. Reports should use this
+    /// number, so that they agree with the admission decisions.
+    pub num_seats: Option<usize>,
+}
+//
+impl Roster {
+    /// Truth that nobody is listed in this roster
+    pub fn is_empty(&self) -> bool {
+        self.accepted.is_empty() && self.waitlisted.is_empty()
+    }
+
+    /// Everyone listed in this roster, with their admission status and rank
+    ///
+    /// Accepted people come first, then waitlisted people. The rank is a
+    /// person's 1-based position in the accepted or waiting list.
+    pub fn ranked(&self) -> impl Iterator<Item = (PersonId, Admission, usize)> + '_ {
+        let accepted = self
+            .accepted
+            .iter()
+            .enumerate()
+            .map(|(idx, &person_id)| (person_id, Admission::Accepted, idx + 1));
+        let waitlisted = self
+            .waitlisted
+            .iter()
+            .enumerate()
+            .map(|(idx, &(person_id, reason))| (person_id, Admission::Waitlisted(reason), idx + 1));
+        accepted.chain(waitlisted)
+    }
+
+    /// Admission status of someone, if they are listed in this roster
+    pub fn status(&self, person_id: PersonId) -> Option<Admission> {
+        if self.accepted.contains(&person_id) {
+            return Some(Admission::Accepted);
+        }
+        self.waitlisted
+            .iter()
+            .find(|(waitlisted_id, _reason)| *waitlisted_id == person_id)
+            .map(|&(_, reason)| Admission::Waitlisted(reason))
+    }
+
+    /// Only keep people for which a predicate is true
+    ///
+    /// This does not change anyone's admission status, people who were
+    /// waitlisted remain waitlisted even if accepted people are removed.
+    pub fn retain(&mut self, mut predicate: impl FnMut(PersonId) -> bool) {
+        self.accepted.retain(|&person_id| predicate(person_id));
+        self.waitlisted
+            .retain(|&(person_id, _reason)| predicate(person_id));
+    }
+}
+
+/// Admission status of someone who registered to a module
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Admission {
+    /// They got a seat
+    Accepted,
+
+    /// They were put on the waiting list
+    Waitlisted(WaitlistReason),
+}
+//
+impl Admission {
+    /// Short machine-readable label of this status
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Accepted => "accepted",
+            Self::Waitlisted(_) => "waitlist",
+        }
+    }
+}
+
+/// Why someone was put on a module's waiting list
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WaitlistReason {
+    /// The module was full
+    Capacity,
+
+    /// The module had free seats, but the person's affiliation used up its quota
+    AffiliationQuota,
+
+    /// The person was put on the waiting list in Indico
+    RegistrationState,
+}
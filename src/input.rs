@@ -0,0 +1,859 @@
+//! Input data from Indico, and from the configuration files that complement it
+
+use crate::{
+    output::{IdentityFormat, Markup},
+    registrations::{normalize_email, DataWarning, ModuleId, Registrations},
+};
+use log::{debug, warn};
+use regex::Regex;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::{Display, Write},
+    io::{BufRead, Read},
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+
+/// Indico registration record
+///
+/// The optional fields are those which I'm not using yet, but which sounded
+/// interesting and which I'm considering for future use.
+#[allow(unused)]
+#[derive(Debug, Deserialize)]
+pub struct CSVRecord {
+    #[serde(rename = "ID")]
+    pub id: Option<usize>,
+    #[serde(flatten)]
+    pub identity: Identity,
+    /// Module choices, separated by `;` (may be absent if
+    /// [`ColumnMapping::modules`] is used instead)
+    #[serde(rename = "Choice of modules", default)]
+    pub choice_of_modules: Box<str>,
+    /// Registration time, parsed from the "Registration date" column as
+    /// directed by [`ColumnMapping`]
+    #[serde(skip, default = "unparsed_registration_time")]
+    pub registration_time: OffsetDateTime,
+    #[serde(rename = "Registration state")]
+    pub registration_state: Option<Box<str>>,
+    /// Registration priority, from the optional user-specified priority column
+    #[serde(skip)]
+    pub priority: Option<i64>,
+}
+
+/// Basic information about people that we want to display in the end
+#[derive(Debug, Deserialize)]
+pub struct Identity {
+    #[serde(rename = "Name")]
+    pub name: Box<str>,
+    #[serde(rename = "Email Address")]
+    pub email_address: Box<str>,
+    #[serde(rename = "Affiliation")]
+    pub affiliation: Box<str>,
+}
+//
+impl Identity {
+    /// Affiliation, with well-known long spellings replaced by a short name
+    pub fn simplified_affiliation(&self) -> &str {
+        self.simplify_affiliation().unwrap_or(&self.affiliation)
+    }
+
+    /// Short name of the affiliation, if a simplification rule matched it
+    ///
+    /// See [`simplify_affiliation()`] for the rules that apply.
+    pub fn simplify_affiliation(&self) -> Option<&str> {
+        simplify_affiliation(&self.affiliation)
+    }
+
+    /// Render this identity in some output markup language
+    pub fn render(&self, markup: Markup, format: IdentityFormat) -> String {
+        let name_and_email = match format {
+            IdentityFormat::NameEmailAffiliation | IdentityFormat::NameEmail => {
+                match EMAIL_HASH_SALT.get() {
+                    Some(salt) => {
+                        format!("{} <{}>", self.name, hash_email(salt, &self.email_address))
+                    }
+                    None => format!("{} <{}>", self.name, self.email_address),
+                }
+            }
+            IdentityFormat::NameOnly | IdentityFormat::NameAffiliation => self.name.to_string(),
+        };
+        let mut result = match markup {
+            Markup::Markdown => format!("`{name_and_email}`"),
+            Markup::Plain => name_and_email,
+            Markup::Html => format!("<code>{}</code>", markup.escape(&name_and_email)),
+        };
+        if format.shows_affiliation() && !self.affiliation.is_empty() {
+            write!(
+                result,
+                " from {}",
+                markup.escape(self.simplified_affiliation())
+            )
+            .expect("Writing to a String can't fail");
+        }
+        result
+    }
+}
+//
+impl Display for Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.render(Markup::Plain, IdentityFormat::default()))
+    }
+}
+
+/// Placeholder registration time of records that were just deserialized,
+/// before the registration date column is parsed
+fn unparsed_registration_time() -> OffsetDateTime {
+    OffsetDateTime::UNIX_EPOCH
+}
+
+/// Date/time format used by Indico
+///
+/// Indico exports usually have fractional seconds, but some don't, so
+/// both forms are accepted. Timestamps without a time zone offset are also
+/// accepted, and are assumed to use [`ColumnMapping::assumed_offset`]. Dates
+/// without a time can be accepted too, see [`ColumnMapping::allow_date_only`].
+mod indico_datetime {
+    use super::ColumnMapping;
+    use time::{
+        error::Parse, format_description::FormatItem, macros::format_description, Date,
+        OffsetDateTime, PrimitiveDateTime, Time, UtcOffset,
+    };
+
+    /// Format with fractional seconds
+    const WITH_SUBSECOND: &[FormatItem<'_>] = format_description!(
+        "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond][offset_hour sign:mandatory]:[offset_minute]"
+    );
+
+    /// Format without fractional seconds
+    const WITHOUT_SUBSECOND: &[FormatItem<'_>] = format_description!(
+        "[year]-[month]-[day] [hour]:[minute]:[second][offset_hour sign:mandatory]:[offset_minute]"
+    );
+
+    /// Format with fractional seconds, but no offset
+    const NAIVE_WITH_SUBSECOND: &[FormatItem<'_>] =
+        format_description!("[year]-[month]-[day] [hour]:[minute]:[second].[subsecond]");
+
+    /// Format without fractional seconds nor offset
+    const NAIVE_WITHOUT_SUBSECOND: &[FormatItem<'_>] =
+        format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+
+    /// Format without a time, see [`ColumnMapping::allow_date_only`]
+    const DATE_ONLY: &[FormatItem<'_>] = format_description!("[year]-[month]-[day]");
+
+    pub fn parse(datetime: &str, columns: &ColumnMapping) -> Result<OffsetDateTime, Parse> {
+        OffsetDateTime::parse(datetime, WITH_SUBSECOND)
+            .or_else(|_| OffsetDateTime::parse(datetime, WITHOUT_SUBSECOND))
+            .or_else(|e| {
+                let assumed_offset = columns.assumed_offset.unwrap_or(UtcOffset::UTC);
+                PrimitiveDateTime::parse(datetime, NAIVE_WITH_SUBSECOND)
+                    .or_else(|_| PrimitiveDateTime::parse(datetime, NAIVE_WITHOUT_SUBSECOND))
+                    .or_else(|e| {
+                        if columns.allow_date_only {
+                            Date::parse(datetime, DATE_ONLY)
+                                .map(|date| PrimitiveDateTime::new(date, Time::MIDNIGHT))
+                        } else {
+                            Err(e)
+                        }
+                    })
+                    .map(|datetime| datetime.assume_offset(assumed_offset))
+                    // Report the error of the usual format, not the fallback
+                    .map_err(|_| e)
+            })
+    }
+}
+
+/// Salt of displayed email hashes, if emails should be hashed, see
+/// [`set_email_hash_salt()`]
+static EMAIL_HASH_SALT: OnceLock<Box<str>> = OnceLock::new();
+
+/// Display a salted hash of email addresses instead of the addresses
+///
+/// The same address, ignoring case, always gets the same hash, so that people
+/// can still be recognized across the output. This can only be done once,
+/// before identities are first rendered.
+pub fn set_email_hash_salt(salt: &str) {
+    EMAIL_HASH_SALT
+        .set(salt.into())
+        .expect("Email hash salt should only be set once");
+}
+
+/// Short salted hash of an email address, as displayed with
+/// [`set_email_hash_salt()`]
+pub fn hash_email(salt: &str, email: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(normalize_email(email).as_bytes());
+    hasher.finalize()[..4]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Parse a time zone offset like "+02:00", or "UTC"
+pub fn parse_utc_offset(offset: &str) -> Result<UtcOffset, String> {
+    let offset = offset.trim();
+    if offset.eq_ignore_ascii_case("utc") || offset == "Z" {
+        return Ok(UtcOffset::UTC);
+    }
+    UtcOffset::parse(
+        offset,
+        time::macros::format_description!("[offset_hour sign:mandatory]:[offset_minute]"),
+    )
+    .map_err(|e| format!("expected an offset like +02:00 ({e})"))
+}
+
+/// CSV columns that are mapped by CSVRecord and Identity
+///
+/// Must be kept in sync with the serde renames of these structs.
+pub const KNOWN_COLUMNS: &[&str] = &[
+    "ID",
+    "Name",
+    "Email Address",
+    "Affiliation",
+    "Choice of modules",
+    "Registration date",
+    "Registration state",
+];
+
+/// Extra CSV columns that the user asked to read
+#[derive(Clone, Debug, Default)]
+pub struct ColumnMapping {
+    /// Column containing an integer registration priority, if any
+    pub priority: Option<String>,
+
+    /// Columns which each contain one module choice, used instead of the
+    /// "Choice of modules" column if not empty
+    pub modules: Vec<String>,
+
+    /// Parse the "Choice of modules" column as a JSON array of module names,
+    /// instead of a list of `;`-separated module names
+    pub modules_json: bool,
+
+    /// Time zone offset of registration times which don't specify one
+    ///
+    /// The default is UTC.
+    pub assumed_offset: Option<UtcOffset>,
+
+    /// Accept registration times which are only a date, like "2024-03-15"
+    ///
+    /// These registrations are assumed to happen at midnight, in the time zone
+    /// of [`ColumnMapping::assumed_offset`].
+    pub allow_date_only: bool,
+}
+
+/// Load CSV records
+///
+/// See [`ColumnMapping`] for the columns that are read on top of those of
+/// [`CSVRecord`].
+pub fn load_raw_records(
+    csv_reader: csv::Reader<impl Read>,
+    columns: &ColumnMapping,
+) -> csv::Result<Vec<CSVRecord>> {
+    read_raw_records(csv_reader, columns)?.collect()
+}
+
+/// Find the input files that match a glob pattern, in alphabetical order
+///
+/// Paths without glob metacharacters match themselves if the file exists.
+pub fn expand_input_glob(pattern: &str) -> csv::Result<Vec<PathBuf>> {
+    let paths = glob::glob(pattern)
+        .map_err(|e| invalid_data(format!("Invalid input path pattern \"{pattern}\" ({e})")))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(std::io::Error::from)?;
+    if paths.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("No input file matches \"{pattern}\""),
+        )
+        .into());
+    }
+    debug!("Input files: {paths:?}");
+    Ok(paths)
+}
+
+/// Skip a preamble of `num_lines` lines before the CSV header
+///
+/// Some exports start with comment lines, like the export date. Skipping
+/// stops early if the input ends.
+pub fn skip_preamble(input: &mut impl BufRead, num_lines: usize) -> std::io::Result<()> {
+    let mut line = Vec::new();
+    for _ in 0..num_lines {
+        line.clear();
+        if input.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        debug!(
+            "Skipping preamble line {:?}",
+            String::from_utf8_lossy(&line).trim_end()
+        );
+    }
+    Ok(())
+}
+
+/// Lazily read out raw CSV registration records
+///
+/// Unlike [`load_raw_records()`], records are parsed one at a time as the
+/// iterator is consumed, so that memory usage does not grow with the size of
+/// the CSV when they are fed straight into [`Registrations::try_new()`].
+///
+/// [`Registrations::try_new()`]: crate::registrations::Registrations::try_new
+pub fn read_raw_records(
+    csv_reader: csv::Reader<impl Read>,
+    columns: &ColumnMapping,
+) -> csv::Result<impl Iterator<Item = csv::Result<CSVRecord>>> {
+    let mut unmapped_columns = Vec::new();
+    let records = read_raw_records_noting_unmapped(csv_reader, columns, &mut unmapped_columns)?;
+    warn_about_unmapped_columns(&unmapped_columns);
+    Ok(records)
+}
+
+/// Like [`read_raw_records()`], but add the CSV columns that this program
+/// doesn't know about to `unmapped_columns` instead of warning about them
+///
+/// This lets callers which read several CSV files warn about unmapped columns
+/// only once, with [`warn_about_unmapped_columns()`].
+pub fn read_raw_records_noting_unmapped(
+    mut csv_reader: csv::Reader<impl Read>,
+    columns: &ColumnMapping,
+    unmapped_columns: &mut Vec<Box<str>>,
+) -> csv::Result<impl Iterator<Item = csv::Result<CSVRecord>>> {
+    debug!("Loading CSV registration records...");
+    let headers = canonicalize_headers(csv_reader.headers()?);
+    for header in headers.iter() {
+        let is_mapped = KNOWN_COLUMNS.contains(&header)
+            || Some(header) == columns.priority.as_deref()
+            || columns.modules.iter().any(|column| column == header);
+        if !is_mapped && !unmapped_columns.iter().any(|column| &**column == header) {
+            unmapped_columns.push(header.into());
+        }
+    }
+    let column_idx = |column: &str, what: &str| {
+        headers
+            .iter()
+            .position(|header| header == column)
+            .ok_or_else(|| invalid_data(format!("No {what} column \"{column}\" in CSV")))
+    };
+    let priority_idx = columns
+        .priority
+        .as_deref()
+        .map(|column| column_idx(column, "priority"))
+        .transpose()?;
+    let module_idxs = columns
+        .modules
+        .iter()
+        .map(|column| column_idx(column, "module"))
+        .collect::<csv::Result<Vec<_>>>()?;
+    let time_idx = column_idx("Registration date", "registration date")?;
+    let columns = columns.clone();
+    Ok(csv_reader.into_records().map(move |record| {
+        let record = record?;
+        let parsed = record.deserialize::<CSVRecord>(Some(&headers));
+        let mut parsed = parsed?;
+        let registration_time = record[time_idx].trim();
+        parsed.registration_time =
+            indico_datetime::parse(registration_time, &columns).map_err(|e| {
+                invalid_data(format!(
+                    "Invalid registration date {registration_time:?} of {} ({e})",
+                    parsed.identity.email_address
+                ))
+            })?;
+        debug!("- {parsed:#?}");
+        if columns.modules_json && !parsed.choice_of_modules.trim().is_empty() {
+            let modules =
+                serde_json::from_str::<Vec<String>>(&parsed.choice_of_modules).map_err(|e| {
+                    invalid_data(format!(
+                        "Choice of modules {:?} of {} is not a JSON array of strings ({e})",
+                        parsed.choice_of_modules, parsed.identity.email_address
+                    ))
+                })?;
+            parsed.choice_of_modules = join_module_choices(modules.iter().map(String::as_str));
+        }
+        if let Some(idx) = priority_idx {
+            parsed.priority = parse_priority(&record[idx])?;
+        }
+        if !module_idxs.is_empty() {
+            parsed.choice_of_modules =
+                join_module_choices(module_idxs.iter().map(|&idx| &record[idx]));
+        }
+        Ok(parsed)
+    }))
+}
+
+/// Join module names into Indico's "Choice of modules" format
+///
+/// This is the reverse of [`split_module_choices()`], so semicolons in module
+/// names are escaped. Empty module names are skipped.
+///
+/// [`split_module_choices()`]: crate::registrations::split_module_choices
+fn join_module_choices<'a>(modules: impl Iterator<Item = &'a str>) -> Box<str> {
+    modules
+        .map(str::trim)
+        .filter(|module| !module.is_empty())
+        .map(|module| module.replace(';', "\\;"))
+        .collect::<Vec<_>>()
+        .join(";")
+        .into()
+}
+
+/// Parse the contents of the priority column, where empty means unspecified
+pub fn parse_priority(priority: &str) -> csv::Result<Option<i64>> {
+    let priority = priority.trim();
+    if priority.is_empty() {
+        return Ok(None);
+    }
+    priority
+        .parse()
+        .map(Some)
+        .map_err(|e| invalid_data(format!("Invalid priority \"{priority}\" ({e})")))
+}
+
+/// Report invalid input data that the CSV parser itself did not catch
+pub fn invalid_data(message: String) -> csv::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message).into()
+}
+
+/// Give known CSV columns the exact spelling that CSVRecord expects
+///
+/// Header matching is case-insensitive and ignores surrounding whitespace,
+/// as spreadsheet tools sometimes change the casing of headers.
+pub fn canonicalize_headers(headers: &csv::StringRecord) -> csv::StringRecord {
+    headers
+        .iter()
+        .map(|header| {
+            KNOWN_COLUMNS
+                .iter()
+                .find(|known| known.eq_ignore_ascii_case(header.trim()))
+                .map_or(header, |known| *known)
+        })
+        .collect()
+}
+
+/// Warn about CSV columns that this program doesn't know about
+///
+/// These are silently ignored during deserialization, which could hide the
+/// fact that Indico changed the layout of its CSV export.
+pub fn warn_about_unmapped_columns(unmapped_columns: &[Box<str>]) {
+    if unmapped_columns.is_empty() {
+        return;
+    }
+    warn!(
+        "Ignoring {} CSV column(s) that this program doesn't know about: {}",
+        unmapped_columns.len(),
+        unmapped_columns
+            .iter()
+            .map(|header| format!("\"{header}\""))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    debug!("Known CSV columns are: {KNOWN_COLUMNS:?}");
+}
+
+/// Load the list of people who actually attended
+///
+/// This is a text file with one email address per line. Blank lines are
+/// ignored.
+pub fn load_attendance(path: &str) -> std::io::Result<HashSet<Box<str>>> {
+    debug!("Loading attendance list...");
+    load_email_list(path)
+}
+
+/// Load a list of email addresses, normalized with [`normalize_email()`]
+///
+/// This is a text file with one email address per line. Blank lines are
+/// ignored.
+pub fn load_email_list(path: &str) -> std::io::Result<HashSet<Box<str>>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(normalize_email)
+        .collect())
+}
+
+/// Load the latest registration time seen by the previous run, if any
+///
+/// This is a text file holding an RFC 3339 timestamp, as written by
+/// [`save_last_run()`](crate::output::save_last_run). If the file doesn't
+/// exist yet, this is the first run and `None` is returned.
+pub fn load_last_run(path: &str) -> csv::Result<Option<OffsetDateTime>> {
+    debug!("Loading the state of the previous run...");
+    let state = match std::fs::read_to_string(path) {
+        Ok(state) => state,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            debug!("- No previous run, everyone is new");
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let state = state.trim();
+    OffsetDateTime::parse(state, &Rfc3339)
+        .map(Some)
+        .map_err(|e| invalid_data(format!("Invalid state file {path} ({e})")))
+}
+
+/// Load a canonical list of modules, in order
+///
+/// This is a text file with one module name per line, as in the Indico CSV or
+/// as displayed. Blank lines and lines starting with `#` are ignored, and
+/// unknown modules are ignored with a warning.
+pub fn load_module_list(
+    path: &str,
+    registrations: &Registrations,
+) -> std::io::Result<Vec<ModuleId>> {
+    debug!("Loading canonical module list...");
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|module| registrations.find_module(module, "ordering"))
+        .collect())
+}
+
+/// Module capacity record
+#[derive(Debug, Deserialize)]
+pub struct CapacityRecord {
+    #[serde(rename = "Module")]
+    pub module: Box<str>,
+    #[serde(rename = "Capacity")]
+    pub capacity: Box<str>,
+}
+
+/// Record of the affiliation hierarchy
+#[derive(Debug, Deserialize)]
+pub struct AffiliationParentRecord {
+    #[serde(rename = "Affiliation")]
+    pub affiliation: Box<str>,
+    #[serde(rename = "Parent")]
+    pub parent: Box<str>,
+}
+
+/// Expected module registration count record
+#[derive(Debug, Deserialize)]
+pub struct ExpectedCountRecord {
+    #[serde(rename = "Module")]
+    pub module: Box<str>,
+    #[serde(rename = "Expected")]
+    pub expected: usize,
+}
+
+/// Per-affiliation module quota record
+#[derive(Debug, Deserialize)]
+pub struct QuotaRecord {
+    #[serde(rename = "Module")]
+    pub module: Box<str>,
+    #[serde(rename = "Affiliation")]
+    pub affiliation: Box<str>,
+    #[serde(rename = "Quota")]
+    pub quota: usize,
+}
+
+/// Registration state alias record
+#[derive(Debug, Deserialize)]
+pub struct StateAliasRecord {
+    #[serde(rename = "State")]
+    pub state: Box<str>,
+    #[serde(rename = "Meaning")]
+    pub meaning: StateMeaning,
+}
+
+/// Canonical meaning of an Indico registration state
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StateMeaning {
+    /// The person should be admitted as usual
+    Active,
+
+    /// The person should go straight to waiting lists
+    Waitlist,
+
+    /// The person cancelled their registration and should not be listed
+    Cancelled,
+}
+
+/// Load a CSV file which maps affiliations to a parent group
+///
+/// This file should have "Affiliation" and "Parent" columns, where the
+/// affiliation is a simplified affiliation.
+pub fn load_affiliation_hierarchy(path: &str) -> csv::Result<HashMap<Box<str>, Box<str>>> {
+    debug!("Loading affiliation hierarchy...");
+    csv::Reader::from_path(path)?
+        .deserialize()
+        .map(|record| {
+            let AffiliationParentRecord {
+                affiliation,
+                parent,
+            } = record?;
+            debug!("- {affiliation} => {parent}");
+            Ok((affiliation, parent))
+        })
+        .collect()
+}
+
+/// Extra information about a module, from the module metadata file
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+pub struct ModuleMetadata {
+    /// Short description of the module
+    pub description: Option<Box<str>>,
+
+    /// Where the module takes place
+    pub location: Option<Box<str>>,
+}
+
+/// Load a TOML file of module metadata
+///
+/// This file has one table per module, named after the module as in the
+/// Indico CSV or as displayed, with optional `description` and `location`
+/// keys. Metadata about unknown modules is ignored with a warning.
+///
+/// Several tables may designate the same module, e.g. one using the Indico
+/// name and one using the displayed name. If their metadata differs, a
+/// [`DataWarning::ConflictingModuleMetadata`] is recorded and the table
+/// whose name comes first in alphabetical order is used.
+pub fn load_module_metadata(
+    path: &str,
+    registrations: &mut Registrations,
+) -> csv::Result<HashMap<ModuleId, ModuleMetadata>> {
+    debug!("Loading module metadata...");
+    let metadata =
+        toml::from_str::<BTreeMap<String, ModuleMetadata>>(&std::fs::read_to_string(path)?)
+            .map_err(|e| invalid_data(format!("Invalid module metadata file ({e})")))?;
+    let mut result = HashMap::<ModuleId, ModuleMetadata>::new();
+    for (module, metadata) in metadata {
+        debug!("- {module}: {metadata:?}");
+        let Some(module_id) = registrations.find_module(&module, "metadata") else {
+            continue;
+        };
+        match result.get(&module_id) {
+            Some(previous) if *previous != metadata => {
+                let module = registrations.modules[module_id].indico_name.as_ref().into();
+                registrations.warn(DataWarning::ConflictingModuleMetadata { module });
+            }
+            Some(_) => {}
+            None => {
+                result.insert(module_id, metadata);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Load a CSV file of expected module registration counts
+pub fn load_expected_counts(path: &str) -> csv::Result<Vec<ExpectedCountRecord>> {
+    debug!("Loading expected registration counts...");
+    csv::Reader::from_path(path)?.deserialize().collect()
+}
+
+/// User-specified affiliation simplification rules, see [`AffiliationRules`]
+static AFFILIATION_RULES: OnceLock<AffiliationRules> = OnceLock::new();
+
+/// Results of [`simplify_affiliation()`], for each distinct affiliation
+static SIMPLIFICATION_CACHE: Mutex<Option<HashMap<Box<str>, Option<&'static str>>>> =
+    Mutex::new(None);
+
+/// Short name of an affiliation, if a simplification rule matched it
+///
+/// Rules are tried in the following order of precedence, so that the rules
+/// of [`AffiliationRules`] can override the built-in ones:
+///
+/// 1. Exact matches from [`AffiliationRules::exact`]
+/// 2. Regexes from [`AffiliationRules::regexes`], in file order
+/// 3. Built-in simplifications of well-known long spellings
+/// 4. Results of [`AffiliationRules::run_normalizer()`]
+///
+/// Results are cached for each distinct affiliation.
+pub fn simplify_affiliation(affiliation: &str) -> Option<&'static str> {
+    let mut cache = SIMPLIFICATION_CACHE
+        .lock()
+        .expect("Simplification cache should not be poisoned");
+    let cache = cache.get_or_insert_with(HashMap::new);
+    if let Some(&simplified) = cache.get(affiliation) {
+        return simplified;
+    }
+    let simplified = simplify_affiliation_uncached(affiliation);
+    cache.insert(affiliation.into(), simplified);
+    simplified
+}
+
+/// Implementation of [`simplify_affiliation()`], without caching
+fn simplify_affiliation_uncached(affiliation: &str) -> Option<&'static str> {
+    static BUILTIN_AFFILIATIONS: OnceLock<HashMap<Box<str>, Arc<str>>> = OnceLock::new();
+    let builtin_affiliations = BUILTIN_AFFILIATIONS.get_or_init(|| {
+        let mut result = HashMap::new();
+        let ijclab = Arc::<str>::from("IJCLab");
+        result.insert("Laboratoire de Physique des 2 infinis Irène Joliot-Curie, Université Paris-Saclay, CNRS-IN2P3. Université Paris-Saclay, CNRS-IN2P3".into(), ijclab.clone());
+        result.insert("IJCLAB - IN2P3 - CNRS".into(), ijclab.clone());
+        result.insert("IJCLab - IN2P3 - CNRS".into(), ijclab.clone());
+        result
+    });
+    let rules = AFFILIATION_RULES.get();
+    if let Some(rules) = rules {
+        if let Some(simplified) = rules.exact.get(affiliation) {
+            return Some(simplified);
+        }
+        if let Some((_, simplified)) = rules
+            .regexes
+            .iter()
+            .find(|(regex, _)| regex.is_match(affiliation))
+        {
+            return Some(simplified);
+        }
+    }
+    if let Some(simplified) = builtin_affiliations.get(affiliation) {
+        return Some(simplified);
+    }
+    rules
+        .and_then(|rules| rules.normalized.get(affiliation))
+        .map(|simplified| &**simplified)
+}
+
+/// Affiliation simplification rules, complementing the built-in ones
+///
+/// Exact matches are tried first, then regexes in the order where they were
+/// specified. An affiliation which matches a regex is entirely replaced. See
+/// [`simplify_affiliation()`] for how these rules combine with built-in ones.
+#[derive(Debug, Default)]
+pub struct AffiliationRules {
+    /// Affiliations that are replaced when they match exactly
+    pub exact: HashMap<Box<str>, Box<str>>,
+
+    /// Regexes and the simplified affiliation that replaces their matches
+    pub regexes: Vec<(Regex, Box<str>)>,
+
+    /// Affiliations that were renamed by an external normalizer, see
+    /// [`AffiliationRules::run_normalizer()`]
+    pub normalized: HashMap<Box<str>, Box<str>>,
+}
+//
+impl AffiliationRules {
+    /// Load simplification rules from a file
+    ///
+    /// Each non-empty line has the form `LONG NAME => SHORT NAME`, and lines
+    /// starting with `#` are comments. If the long name starts with `^`, it is
+    /// a regex, otherwise it must match the affiliation exactly.
+    pub fn load(path: &str) -> csv::Result<Self> {
+        debug!("Loading affiliation simplification rules...");
+        let mut result = Self::default();
+        for line in std::fs::read_to_string(path)?.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((long, short)) = line.split_once("=>") else {
+                return Err(invalid_data(format!(
+                    "Invalid affiliation rule \"{line}\" (expected \"LONG => SHORT\")"
+                )));
+            };
+            let (long, short) = (long.trim(), short.trim());
+            debug!("- {long} => {short}");
+            if long.starts_with('^') {
+                let regex = Regex::new(long).map_err(|e| {
+                    invalid_data(format!("Invalid affiliation regex \"{long}\" ({e})"))
+                })?;
+                result.regexes.push((regex, short.into()));
+            } else {
+                result.exact.insert(long.into(), short.into());
+            }
+        }
+        Ok(result)
+    }
+
+    /// Normalize affiliations with an external command
+    ///
+    /// The command is run once by the system shell. It receives each distinct
+    /// affiliation on its own line of standard input, and must print the
+    /// matching normalized affiliation on each line of standard output.
+    /// Normalized affiliations are used when no other rule matched. If the
+    /// command fails, a warning is emitted and affiliations are left as is.
+    pub fn run_normalizer<'a>(
+        &mut self,
+        command: &str,
+        affiliations: impl Iterator<Item = &'a str>,
+    ) {
+        let affiliations = affiliations
+            .filter(|affiliation| !affiliation.trim().is_empty())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        debug!(
+            "Normalizing {} affiliations with \"{command}\"...",
+            affiliations.len()
+        );
+        match run_line_filter(command, &affiliations) {
+            Ok(normalized) => {
+                for (affiliation, normalized) in affiliations.into_iter().zip(normalized) {
+                    let normalized = normalized.trim();
+                    if !normalized.is_empty() && normalized != affiliation {
+                        debug!("- {affiliation} => {normalized}");
+                        self.normalized
+                            .insert(affiliation.into(), normalized.into());
+                    }
+                }
+            }
+            Err(e) => warn!(
+                "Affiliation normalizer \"{command}\" failed, affiliations are left as is ({e})"
+            ),
+        }
+    }
+
+    /// Use these rules in [`Identity::simplified_affiliation()`]
+    ///
+    /// This can only be done once, before affiliations are first queried.
+    pub fn install(self) {
+        AFFILIATION_RULES
+            .set(self)
+            .expect("Affiliation rules should only be installed once");
+        *SIMPLIFICATION_CACHE
+            .lock()
+            .expect("Simplification cache should not be poisoned") = None;
+    }
+}
+
+/// Pipe lines through a shell command, and collect one output line per input
+fn run_line_filter(command: &str, lines: &[&str]) -> std::io::Result<Vec<String>> {
+    use std::{
+        io::Write,
+        process::{Command, Stdio},
+    };
+    #[cfg(windows)]
+    let mut shell = {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    };
+    #[cfg(not(windows))]
+    let mut shell = {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let mut child = shell
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let input = lines
+        .iter()
+        .map(|line| line.replace(['\r', '\n'], " ") + "\n")
+        .collect::<String>();
+    let mut stdin = child.stdin.take().expect("Standard input should be piped");
+    // Feed input from another thread, so that the command can't block us by
+    // filling up its output pipe before it has read all of its input
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    writer.join().expect("Writer thread should not panic")?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!("command {}", output.status)));
+    }
+    let output = String::from_utf8(output.stdout).map_err(std::io::Error::other)?;
+    let output = output.lines().map(str::to_owned).collect::<Vec<_>>();
+    if output.len() != lines.len() {
+        return Err(std::io::Error::other(format!(
+            "expected {} lines of output, got {}",
+            lines.len(),
+            output.len()
+        )));
+    }
+    Ok(output)
+}
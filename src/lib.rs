@@ -0,0 +1,9 @@
+//! Translate Indico's per-user registrations into per-course registrations
+//!
+//! This library contains the logic behind the `indico-transpose` program, so
+//! that it can be reused by other tools.
+
+pub mod admission;
+pub mod input;
+pub mod output;
+pub mod registrations;
@@ -1,276 +1,1566 @@
-use clap::Parser;
-use log::{debug, warn};
+use clap::{
+    parser::ValueSource, ArgGroup, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
+use indico_transpose::{
+    admission::{Capacity, Limits, Roster},
+    input::{
+        expand_input_glob, invalid_data, load_affiliation_hierarchy, load_attendance,
+        load_expected_counts, load_last_run, load_module_list, load_module_metadata,
+        parse_utc_offset, set_email_hash_salt, AffiliationRules, ColumnMapping,
+    },
+    output::{
+        parse_time_format, render_emails, save_last_run, write_affiliation_rosters,
+        write_atomically, write_attendance_report, write_capacity_summary, write_count_mismatches,
+        write_daily_overloads, write_diff, write_explanation, write_hidden_registrants,
+        write_id_anomalies, write_late_registrations, write_mailmerge_csv, write_module_query,
+        write_nearing_capacity, write_new_registrations, write_out_of_range_registrations,
+        write_paginated_rosters, write_possible_duplicates, write_rosters, write_rosters_json,
+        write_run_report, write_schedules, write_stats, write_summary_header,
+        write_top_registrants, write_unsimplified_affiliations, write_vcards, DisplayOptions,
+        IdentityFormat, Locale, OutputFormat,
+    },
+    registrations::{
+        DataWarning, NameLayout, ProcessingOptions, RegistrationWindow, Registrations,
+        RegistrationsBuilder, TimeUnit, UnscheduledPlacement, DEFAULT_YEAR,
+    },
+};
 use regex::Regex;
-use serde::Deserialize;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use std::{
-    collections::{BTreeMap, HashMap},
-    fmt::Display,
     fs::File,
-    rc::Rc,
-    sync::{Arc, OnceLock},
+    io::{BufRead, BufReader, Cursor, IsTerminal, Write},
+    path::Path,
+    sync::Mutex,
 };
-use time::{Date, Month, OffsetDateTime, Time};
+use time::{format_description::OwnedFormatItem, UtcOffset};
 
 // === CLI starts here ===
 
 /// Translate Indico's per-user registrations into per-course registrations
 #[derive(Parser)]
+#[command(group(ArgGroup::new("capacity_source").multiple(true)))]
 struct Args {
+    /// Alternate operation to perform instead of transposing registrations
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to Indico CSV
+    ///
+    /// This may also be a glob pattern like `exports/*.csv`, in which case
+    /// the records of all matching files are processed as if they were part
+    /// of a single CSV, with files taken in alphabetical order.
     #[arg(default_value_t = String::from("registrations.csv".to_owned()))]
     input_path: String,
+
+    /// List people and modules in the order of the Indico CSV
+    ///
+    /// People are then listed in CSV row order instead of registration
+    /// order, and modules in the order where they are first seen instead of
+    /// start time order. This is mostly useful to compare with Indico's own
+    /// ordering. Waiting lists follow the same order.
+    #[arg(long)]
+    preserve_input_order: bool,
+
+    /// Number of preamble lines to skip before the CSV header of each input
+    #[arg(long, default_value_t = 0)]
+    skip_lines: usize,
+
+    /// Only process the first N registration records, for quick testing
+    ///
+    /// Results are then partial, and should not be trusted for anything but
+    /// trying out other options on a large input.
+    #[arg(long, value_name = "N")]
+    sample: Option<usize>,
+
+    /// Path to a TOML file of default options
+    ///
+    /// Each key of this file is the long name of a command-line option, like
+    /// `format = "html"` for `--format html`. The input path can be set with
+    /// `input-path`, flags are set with `true`, and options that accept
+    /// several values can be given an array. Options from the command line
+    /// take precedence over this file, including over the options of this
+    /// file that they conflict with.
+    #[arg(long)]
+    config: Option<String>,
+
+    /// Write the output to this file instead of the standard output
+    ///
+    /// The file is written atomically: it is either left untouched or
+    /// entirely replaced, never partially written.
+    #[arg(long, short)]
+    output: Option<String>,
+
+    /// Split module rosters into files of at most N modules each
+    ///
+    /// The parts are named `part-001.md`, `part-002.md`... and written next to
+    /// the `--output` file, which then links to them instead of containing the
+    /// rosters. This keeps huge rosters manageable.
+    #[arg(long, value_name = "N", requires = "output")]
+    paginate: Option<usize>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+
+    /// Information displayed about each person
+    ///
+    /// The Markdown table format always has separate name and affiliation
+    /// columns, and never displays email addresses.
+    #[arg(long, value_enum, default_value_t)]
+    identity_format: IdentityFormat,
+
+    /// Never display affiliations, only names and email addresses
+    ///
+    /// This is a shorthand for `--identity-format name-email`, which also
+    /// leaves the affiliation column of Markdown tables empty.
+    #[arg(long, conflicts_with = "identity_format")]
+    no_affiliation: bool,
+
+    /// Display a short salted hash of each email address instead of the
+    /// address itself
+    ///
+    /// The same address always gets the same hash, so that people can still
+    /// be told apart and followed across the output without exposing their
+    /// address. Exported files, like `--mailmerge-csv`, keep the addresses.
+    #[arg(long)]
+    hash_emails: bool,
+
+    /// Salt of the email hashes, see `--hash-emails`
+    ///
+    /// Without a secret salt, hashes of known addresses can be recognized.
+    #[arg(long, requires = "hash_emails", default_value = "")]
+    email_salt: String,
+
+    /// Language of output headings and other static output text
+    ///
+    /// Data from the input files, like module names, is not translated.
+    #[arg(long, value_enum, default_value_t)]
+    locale: Locale,
+
+    /// Shift all headings by this many levels
+    ///
+    /// This is useful when embedding the output in a larger document. For
+    /// example, an offset of 1 turns top-level headings into second-level
+    /// headings. Headings never go deeper than level 6.
+    #[arg(long, default_value_t = 0)]
+    heading_offset: usize,
+
+    /// Path to a CSV file of module capacities
+    ///
+    /// This file should have a "Module" column, containing module names as
+    /// they appear in the Indico CSV, and a "Capacity" column, containing the
+    /// maximal number of attendees for this module. The capacity may also be
+    /// a percentage of the people who registered to the module, like "50%"
+    /// (rounded down, but at least 1). People who register once a module is
+    /// full are put on its waiting list.
+    #[arg(long, group = "capacity_source")]
+    capacities: Option<String>,
+
+    /// Path to a list of people who are guaranteed a seat, like invited
+    /// speakers
+    ///
+    /// This is a text file with one email address per line. These people come
+    /// first in every module they chose, and are accepted even if the module
+    /// is full, unless their registration was cancelled. They are marked as
+    /// "(VIP)" in the rosters.
+    #[arg(long, value_name = "FILE")]
+    vip: Option<String>,
+
+    /// Set the capacity of one module, like "Rust intro 03/15, 14h00=20"
+    ///
+    /// This may be repeated, and overrides the capacities file if any. The
+    /// capacity may also be a percentage, as in the capacities file.
+    #[arg(long, value_name = "MODULE=N", value_parser = Capacity::parse_override, group = "capacity_source")]
+    capacity: Vec<(Box<str>, Capacity)>,
+
+    /// Flag modules whose registrations exceed this percentage of their
+    /// capacity, before they are full
+    ///
+    /// These modules are listed in a "Nearing capacity" section at the top of
+    /// the output, as an early warning.
+    #[arg(long, value_name = "P", requires = "capacity_source")]
+    capacity_warn_pct: Option<f64>,
+
+    /// Path to a CSV file of per-affiliation module quotas
+    ///
+    /// This file should have "Module", "Affiliation" and "Quota" columns,
+    /// which specify how many people from a given (simplified) affiliation
+    /// may attend a given module. Extra people from this affiliation are put
+    /// on the module's waiting list, even if the module is not full.
+    #[arg(long)]
+    affiliation_quota: Option<String>,
+
+    /// Favor people with fewer seats in oversubscribed modules
+    ///
+    /// Oversubscribed modules, which have more registrations than their
+    /// capacity, are allocated in start time order. Once someone got a seat
+    /// in this many oversubscribed modules, they are only admitted to the
+    /// next ones if seats remain after everyone else was served.
+    #[arg(long)]
+    fair_rotation: Option<usize>,
+
+    /// Treat the order of each person's module choices as a preference order
+    ///
+    /// Full modules then give seats to people who listed them first, then to
+    /// people who listed them second, and so on, before falling back to
+    /// registration order.
+    #[arg(long)]
+    respect_choice_order: bool,
+
+    /// Registration state of people who are on the waiting list in Indico
+    ///
+    /// People with this registration state are put on the waiting list of
+    /// every module they chose, without taking up a seat. Other people are
+    /// admitted as usual. Use an empty string to disable this.
+    #[arg(long, default_value_t = String::from("Waiting list"))]
+    waitlist_state: String,
+
+    /// Path to a CSV file that maps registration states to their meaning
+    ///
+    /// This file should have "State" and "Meaning" columns. The meaning can
+    /// be "active" (admitted as usual), "waitlist" (put on every waiting
+    /// list, like `--waitlist-state`) or "cancelled" (not listed at all).
+    /// States are matched case-insensitively, and take precedence over
+    /// `--waitlist-state`.
+    #[arg(long)]
+    state_aliases: Option<String>,
+
+    /// Path to a file of extra affiliation simplification rules
+    ///
+    /// Each line has the form `LONG NAME => SHORT NAME`, where the long name
+    /// must either match the affiliation exactly, or be a regex starting with
+    /// `^` (e.g. `^.*IJCLab.*$ => IJCLab`). Exact rules are tried first, then
+    /// regexes in file order, then the built-in rules, so that this file can
+    /// override them. Lines starting with `#` are ignored.
+    #[arg(long)]
+    affiliations: Option<String>,
+
+    /// Shell command that normalizes affiliations
+    ///
+    /// This command receives each distinct affiliation on a line of its
+    /// standard input, and must print the normalized affiliation on the
+    /// matching line of its standard output. Normalized affiliations are only
+    /// used when no other simplification rule matched. If the command fails,
+    /// affiliations are left as is.
+    #[arg(long)]
+    affiliation_normalizer: Option<String>,
+
+    /// List the affiliations that no simplification rule matched
+    ///
+    /// These are candidates for new rules in the `--affiliations` file.
+    #[arg(long)]
+    report_unsimplified_affiliations: bool,
+
+    /// Remove the schedule information from displayed module names
+    ///
+    /// Module names in Indico contain the date and time at which the module
+    /// starts, which is used to order modules. Once it has been parsed, this
+    /// information can be removed from the module headers.
+    #[arg(long)]
+    strip_date_from_name: bool,
+
+    /// Round module start times to this many minutes when ordering modules
+    ///
+    /// This lets modules whose start times differ by a minute or so, because
+    /// of different data entry styles, be treated as starting in the same
+    /// time slot. Displayed start times are not rounded.
+    #[arg(long, value_name = "MINUTES")]
+    module_time_granularity: Option<u32>,
+
+    /// Also parse module schedules that use English or French month names
+    ///
+    /// By default, only schedules like "03/15, 14h00" or ISO-8601 dates like
+    /// "2024-03-15T14:00" are recognized. With this flag, schedules like
+    /// "15 March, 14h00" or "15 mars, 14h00" are recognized too.
+    #[arg(long)]
+    month_names: bool,
+
+    /// Modules whose start time should not be parsed, separated by `;`
+    ///
+    /// This is for module titles that contain something which looks like a
+    /// schedule without being one. These modules are treated as having no
+    /// known start time, and their name is never stripped.
+    #[arg(long, value_delimiter = ';', value_name = "MODULE;MODULE;...")]
+    no_parse_modules: Vec<String>,
+
+    /// Merge modules whose names are at least this similar, from 0 to 1
+    ///
+    /// This catches typos in module names that the Indico CSV may contain,
+    /// like "Deep Learnning" for "Deep Learning". Similar modules which start
+    /// at the same time are merged into the one that most people chose, and
+    /// every merge is logged as a warning. A threshold around 0.9 is a good
+    /// start, as lower thresholds can merge genuinely different modules.
+    #[arg(long, value_name = "THRESHOLD", value_parser = parse_similarity)]
+    fuzzy_modules: Option<f64>,
+
+    /// Fail if any module has no known start time
+    ///
+    /// This is useful for pipelines that require a fully scheduled event. The
+    /// check is done once all registrations are loaded, and the error lists
+    /// every module whose start time could not be parsed.
+    #[arg(long)]
+    require_all_scheduled: bool,
+
+    /// Path to a canonical list of modules, used to order modules whose start
+    /// time is unknown
+    ///
+    /// This is a text file with one module name per line. Modules whose start
+    /// time is known are still ordered by start time, but unscheduled modules
+    /// are ordered like in this file instead of by name, and placed as
+    /// directed by `--unscheduled-placement`.
+    #[arg(long, conflicts_with = "preserve_input_order")]
+    modules: Option<String>,
+
+    /// Where unscheduled modules from the `--modules` list go
+    #[arg(long, value_enum, default_value_t, requires = "modules")]
+    unscheduled_placement: UnscheduledPlacement,
+
+    /// Display the parsed start time of each module in its heading
+    ///
+    /// Start times are displayed as written in module names, without any
+    /// time zone conversion.
+    #[arg(long)]
+    show_module_times: bool,
+
+    /// Format of displayed dates and times
+    ///
+    /// This is a format description of the `time` crate, like
+    /// `[day]/[month] [hour]:[minute]`. It applies to module start times and
+    /// registration times. The default is `[year]-[month]-[day] [hour]:[minute]`.
+    #[arg(long, value_parser = parse_time_format, value_name = "SPEC")]
+    time_format: Option<OwnedFormatItem>,
+
+    /// Truncate displayed module names to this many characters
+    ///
+    /// Truncated names end with an ellipsis. This only affects the display,
+    /// modules are still identified by their full name.
+    #[arg(long)]
+    truncate_names: Option<usize>,
+
+    /// Write each module's roster on a single line
+    ///
+    /// Lines look like "Module (3): Alice, Bob, Carol", with the names of
+    /// accepted people in registration order, followed by the waiting list,
+    /// if any.
+    #[arg(long)]
+    compact: bool,
+
+    /// Group modules by track, taken from the prefix of their name
+    ///
+    /// Module names are split at the first occurrence of this separator,
+    /// which is ": " if omitted. For example, "ML: Intro" is displayed as
+    /// "Intro" below an "ML" heading. Modules without the separator are
+    /// grouped at the end.
+    #[arg(long, num_args = 0..=1, default_missing_value = ": ", value_name = "SEPARATOR")]
+    group_by_prefix: Option<String>,
+
+    /// Tag each person as an early, mid or late registrant in rosters
+    ///
+    /// Cohorts are tertiles of registration time, over everyone who
+    /// registered.
+    #[arg(long)]
+    cohorts: bool,
+
+    /// Path to a TOML file of module descriptions and locations
+    ///
+    /// This file has one table per module, named after the module, with
+    /// optional `description` and `location` keys. These are displayed below
+    /// the module's heading.
+    #[arg(long)]
+    module_metadata: Option<String>,
+
+    /// Where the schedule information is located in module names
+    ///
+    /// By default, `--strip-date-from-name` only removes the schedule itself.
+    /// With `date-suffix`, everything from the schedule onwards is removed,
+    /// and with `date-prefix`, everything up to the schedule is removed,
+    /// along with any separator between the schedule and the module title.
+    #[arg(long, value_enum, requires = "strip_date_from_name")]
+    name_layout: Option<NameLayout>,
+
+    /// Year of module schedules which only give a day and month, like
+    /// "03/15, 14h00"
+    #[arg(long, default_value_t = DEFAULT_YEAR)]
+    year: i32,
+
+    /// Time zone offset of registration times which don't specify one
+    ///
+    /// This is written like "+02:00", or "UTC".
+    #[arg(long, value_parser = parse_utc_offset, default_value = "UTC")]
+    assume_offset: UtcOffset,
+
+    /// Accept registration times which are only a date, like "2024-03-15"
+    ///
+    /// Such registrations are assumed to happen at midnight.
+    #[arg(long)]
+    allow_date_only: bool,
+
+    /// Name of a CSV column containing an integer registration priority
+    ///
+    /// If specified, people with a higher priority are listed before people
+    /// with a lower priority, and registration time only breaks ties between
+    /// people of equal priority. An empty cell means priority 0.
+    #[arg(long)]
+    priority_column: Option<String>,
+
+    /// Names of CSV columns which each contain one module choice
+    ///
+    /// Some registration forms have one column per time slot (e.g. "Morning
+    /// module" and "Afternoon module") instead of a single "Choice of
+    /// modules" column. The non-empty values of these columns are then the
+    /// modules that a person chose.
+    #[arg(long, value_delimiter = ',')]
+    module_columns: Vec<String>,
+
+    /// Parse the "Choice of modules" column as a JSON array of module names
+    ///
+    /// Some export configurations write module choices like
+    /// `["Module A", "Module B"]` instead of `Module A; Module B`.
+    #[arg(long, conflicts_with = "module_columns")]
+    module_field_json: bool,
+
+    /// Round registration times down to this unit before ordering people
+    ///
+    /// People whose registration times fall within the same minute, hour or
+    /// day are then considered to have registered at the same time, and are
+    /// ordered by email address.
+    #[arg(long, value_enum)]
+    round_registration_times: Option<TimeUnit>,
+
+    /// Merge registrations that share an email address
+    ///
+    /// The resulting person is registered to every module that was chosen in
+    /// any of the merged registrations, at the earliest registration time.
+    #[arg(long)]
+    dedup: bool,
+
+    /// List the modules chosen by each person, instead of who registered to
+    /// each module
+    #[arg(long)]
+    by_person: bool,
+
+    /// Report people registered to more than this many modules on one day
+    ///
+    /// Modules whose date could not be parsed from their name are not taken
+    /// into account.
+    #[arg(long)]
+    max_per_person_per_day: Option<usize>,
+
+    /// Report registrations made outside of this range of dates
+    ///
+    /// This is written like `2024-01-01..2024-03-31`, bounds included.
+    /// Registrations outside of this window usually come from corrupt
+    /// exports. They are reported, but still processed as usual.
+    #[arg(long, value_parser = RegistrationWindow::parse)]
+    registration_window: Option<RegistrationWindow>,
+
+    /// Path to a CSV file of expected module registration counts
+    ///
+    /// This file should have "Module" and "Expected" columns. Modules whose
+    /// number of registrations differs from the expected count are reported,
+    /// which helps catching import errors.
+    #[arg(long)]
+    expected_counts: Option<String>,
+
+    /// Report duplicate record IDs, and gaps larger than MAX_GAP (10 if
+    /// omitted) between consecutive IDs
+    ///
+    /// These may indicate that several exports were concatenated, or that
+    /// some records were lost. This requires an "ID" column.
+    #[arg(long, num_args = 0..=1, default_missing_value = "10", value_name = "MAX_GAP")]
+    check_ids: Option<usize>,
+
+    /// List the people who chose the most modules, up to this many people
+    #[arg(long)]
+    top_registrants: Option<usize>,
+
+    /// Start the output with the number of persons and modules
+    #[arg(long)]
+    summary_header: bool,
+
+    /// List the people who registered to all of these modules
+    ///
+    /// Module names are separated by `;`, as in Indico's "Choice of modules"
+    /// column, since they usually contain commas. They are matched ignoring
+    /// case and whitespace differences. This replaces the usual output.
+    #[arg(long, value_delimiter = ';', value_name = "MODULE;MODULE;...")]
+    registered_to_all: Vec<String>,
+
+    /// List the people who registered to any of these modules
+    ///
+    /// Module names are given like for `--registered-to-all`. Each person is
+    /// listed once, along with the modules that they chose among these.
+    #[arg(
+        long,
+        value_delimiter = ';',
+        value_name = "MODULE;MODULE;...",
+        conflicts_with = "registered_to_all"
+    )]
+    registered_to_any: Vec<String>,
+
+    /// List the registrants of each affiliation, instead of module rosters
+    ///
+    /// This lets each lab see which of its people registered, and to which
+    /// modules. Affiliations are ordered by name, and their registrants by
+    /// registration time.
+    #[arg(long)]
+    by_affiliation: bool,
+
+    /// Explain how the admissions of the person with this email were decided
+    ///
+    /// This replaces the usual output with a step-by-step account of their
+    /// registration rank, capacity and admission status in each module they
+    /// chose, which helps answer questions about waiting lists.
+    #[arg(long)]
+    explain: Option<String>,
+
+    /// Ask for confirmation before producing output where people are
+    /// put on waiting lists
+    ///
+    /// The number of waitlisted people of each module is displayed first.
+    /// This has no effect if standard input is not a terminal.
+    #[arg(long)]
+    interactive: bool,
+
+    /// Answer yes to the `--interactive` confirmation prompt
+    #[arg(long)]
+    yes: bool,
+
+    /// Append statistics about the registrations to the output
+    ///
+    /// This includes a breakdown of persons per affiliation, and the number
+    /// of distinct affiliations that registered to each module.
+    #[arg(long)]
+    stats: bool,
+
+    /// Path to a CSV file which maps affiliations to parent groups
+    ///
+    /// This file should have "Affiliation" and "Parent" columns, e.g. to map
+    /// laboratories to the institution that they belong to.
+    #[arg(long)]
+    affiliation_hierarchy: Option<String>,
+
+    /// How people are grouped in the per-affiliation statistics
+    ///
+    /// Grouping by parent requires `--affiliation-hierarchy`.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        requires_if("parent", "affiliation_hierarchy")
+    )]
+    stats_group_by: StatsGroupBy,
+
+    /// Only list people from this (simplified) affiliation
+    ///
+    /// Admission to modules is still decided with everyone taken into
+    /// account. Affiliation matching is case-insensitive.
+    #[arg(long)]
+    only_affiliation: Option<String>,
+
+    /// Only display modules whose name matches this regular expression
+    ///
+    /// Admission to modules is still decided with every module taken into
+    /// account. This affects every output, including exported files.
+    #[arg(long)]
+    only_modules: Option<String>,
+
+    /// List people who chose modules, but none of those displayed due to
+    /// `--only-modules`, so that nobody vanishes from the output unnoticed
+    #[arg(long, requires = "only_modules")]
+    report_hidden: bool,
+
+    /// Path to a list of people who actually attended
+    ///
+    /// This is a text file with one email address per line, for example a
+    /// check-in export. Each listed person is then marked as present or
+    /// absent.
+    #[arg(long)]
+    attendance: Option<String>,
+
+    /// Append the attendance rate of each module to the output
+    ///
+    /// This is the fraction of accepted people who attended the module.
+    #[arg(long, requires = "attendance")]
+    attendance_report: bool,
+
+    /// Save every registration to a CSV file at this path, for mail-merge
+    ///
+    /// The file has "email", "name", "module", "rank" and "status" columns,
+    /// where the status is "accepted" or "waitlist" and the rank is the
+    /// person's position in the corresponding list. It is not affected by
+    /// `--only-affiliation`.
+    #[arg(long)]
+    mailmerge_csv: Option<String>,
+
+    /// Save the contact information of registrants to a vCard file at this
+    /// path
+    ///
+    /// There is one contact per email address, with the person's name and
+    /// affiliation.
+    #[arg(long)]
+    vcard: Option<String>,
+
+    /// Save the rosters to a JSON file at this path
+    ///
+    /// Each module has its names, its `count` of registrants and a
+    /// `registrants` array, with the same rank and status as in
+    /// `--mailmerge-csv`. The `total_persons` and `total_modules` fields give
+    /// overall counts. Like `--mailmerge-csv`, this is not affected by
+    /// `--only-affiliation`.
+    #[arg(long)]
+    json_out: Option<String>,
+
+    /// Save the rosters to an Excel workbook at this path
+    ///
+    /// Each module gets a worksheet with rank, name, email, affiliation and
+    /// status columns. Like `--mailmerge-csv`, this is not affected by
+    /// `--only-affiliation`.
+    #[cfg(feature = "xlsx")]
+    #[arg(long)]
+    xlsx_out: Option<String>,
+
+    /// Path to a template for personalized emails, see `--render-emails`
+    ///
+    /// The template is plain text where `{{name}}`, `{{email}}`, `{{module}}`,
+    /// `{{rank}}` and `{{status}}` are replaced by the same information as in
+    /// `--mailmerge-csv`.
+    #[arg(long, requires = "render_emails")]
+    email_template: Option<String>,
+
+    /// Render the `--email-template` into one file per (person, module)
+    /// registration, in this directory
+    #[arg(long, requires = "email_template")]
+    render_emails: Option<String>,
+
+    /// Report registrations that arrived since the previous run
+    ///
+    /// The latest registration time is saved to this state file, and the
+    /// next run lists who registered to each module after that time, at the
+    /// top of the output. The full rosters are still displayed. On the first
+    /// run, when the state file doesn't exist yet, nothing is reported.
+    #[arg(long, value_name = "STATE_FILE")]
+    since_last_run: Option<String>,
+
+    /// Save a summary of this run as a JSON document at this path
+    ///
+    /// This is meant for dashboards. It has a "counts" object with overall
+    /// counts, a "modules" array with the size of each module in display
+    /// order, an "affiliations" array with the number of people from each
+    /// simplified affiliation, and a "warnings" object with the number of
+    /// data quality warnings of each category (see `--warnings-json`).
+    #[arg(long, value_name = "PATH")]
+    report_json: Option<String>,
+
+    /// Save data quality warnings to a JSON file at this path
+    ///
+    /// Each warning has a "category" field (one of "unparsed_module_time",
+    /// "empty_choice_of_modules", "duplicate_email", "invalid_email",
+    /// "conflicting_module_metadata" and "possibly_misspelled_module") and
+    /// extra fields which identify the affected module or person.
+    #[arg(long)]
+    warnings_json: Option<String>,
+
+    /// Report data quality warnings as GitHub Actions annotations on stderr
+    ///
+    /// Each warning is printed as a `::warning title=CATEGORY::MESSAGE`
+    /// workflow command, so that it shows up in the summary of the CI run.
+    #[arg(long)]
+    github_annotations: bool,
+
+    /// Also write warnings and errors to a log file at this path
+    ///
+    /// This is independent of `RUST_LOG`, which keeps controlling which
+    /// messages are printed on stderr.
+    #[arg(long)]
+    warnings_file: Option<String>,
+
+    /// End the output with the SHA-256 hash of the input CSV, as a comment
+    ///
+    /// This records which exact input produced the output. If there are
+    /// several input files, their concatenation is hashed. Input files are
+    /// then loaded into memory at once, instead of being processed as they
+    /// are read.
+    #[arg(long)]
+    input_hash: bool,
+
+    /// Copy the output to the system clipboard instead of printing it
+    ///
+    /// On Linux, the program keeps running until the clipboard contents are
+    /// replaced, as X11 and Wayland clipboards are owned by the program that
+    /// fills them.
+    #[cfg(feature = "clipboard")]
+    #[arg(long, conflicts_with = "output")]
+    clipboard: bool,
+}
+
+/// Alternate operations
+#[derive(Subcommand)]
+enum Command {
+    /// Report who registered to or left each module between two Indico CSVs
+    ///
+    /// Global options like `--format` must come before the subcommand name.
+    Diff {
+        /// Path to the older Indico CSV (or glob pattern)
+        old_path: String,
+
+        /// Path to the newer Indico CSV (or glob pattern)
+        new_path: String,
+    },
+}
+
+/// Grouping of affiliations in statistics
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum StatsGroupBy {
+    /// Group people by (simplified) affiliation
+    #[default]
+    Leaf,
+
+    /// Group people by parent group of their affiliation, as specified by
+    /// `--affiliation-hierarchy`, or by affiliation if it has no parent
+    Parent,
 }
 
 fn main() -> csv::Result<()> {
     // Set up app
-    env_logger::init();
-    let args = Args::parse();
-
-    // Read out raw CSV records
-    let csv_reader = csv::Reader::from_path(args.input_path)?;
-    let raw_records = load_raw_records(csv_reader)?;
-
-    // Translate records into a more exploitable data layout
-    let registrations = Registrations::new(raw_records);
-
-    // Order people by registration time
-    let persons_by_registration_time = registrations
-        .persons
-        .iter()
-        .enumerate()
-        .map(|(person_id, person)| (person.registration_time, person_id))
-        .collect::<BTreeMap<OffsetDateTime, PersonId>>();
-    if log::max_level() >= log::Level::Debug {
-        debug!("People ordered by registration time");
-        for (date, person_id) in &persons_by_registration_time {
-            debug!(
-                "- {} ({})",
-                registrations.persons[*person_id].identity, date
+    let mut args = parse_args()?;
+    if args.no_affiliation {
+        args.identity_format = IdentityFormat::NameEmail;
+    }
+    init_logger(args.warnings_file.as_deref())?;
+
+    // Configure how identities are displayed
+    if args.hash_emails {
+        set_email_hash_salt(&args.email_salt);
+    }
+
+    // Compare snapshots instead of transposing registrations, if requested
+    if let Some(Command::Diff { old_path, new_path }) = &args.command {
+        return diff_snapshots(&args, old_path, new_path);
+    }
+
+    // Load affiliation simplification rules
+    let mut affiliation_rules = args
+        .affiliations
+        .as_deref()
+        .map(AffiliationRules::load)
+        .transpose()?;
+
+    // Read out raw CSV records from every input file
+    let mut hasher = args.input_hash.then(Sha256::new);
+    let mut registrations = load_registrations(&args, &args.input_path, hasher.as_mut())?;
+    let input_hash = hasher.map(|hasher| to_hex(&hasher.finalize()));
+
+    // Install affiliation rules before any simplified affiliation is used
+    if let Some(command) = &args.affiliation_normalizer {
+        affiliation_rules
+            .get_or_insert_with(Default::default)
+            .run_normalizer(
+                command,
+                registrations
+                    .persons
+                    .iter()
+                    .map(|person| &*person.identity.affiliation),
             );
+    }
+    if let Some(rules) = affiliation_rules {
+        rules.install();
+    }
+    let module_metadata = args
+        .module_metadata
+        .as_deref()
+        .map(|path| load_module_metadata(path, &mut registrations))
+        .transpose()?
+        .unwrap_or_default();
+    let registrations = registrations;
+
+    // Make sure that every module has a start time, if required
+    if args.require_all_scheduled {
+        let unscheduled = registrations
+            .modules
+            .iter()
+            .filter(|module| !module.is_scheduled())
+            .map(|module| format!("\"{}\"", module.indico_name))
+            .collect::<Vec<_>>();
+        if !unscheduled.is_empty() {
+            return Err(invalid_data(format!(
+                "Some modules have no known start time: {}",
+                unscheduled.join(", ")
+            )));
         }
     }
 
-    // For each module, produce a matching ordered list of who registered
-    let mut module_to_ordered_persons =
-        HashMap::<ModuleId, Vec<PersonId>>::with_capacity(registrations.modules.len());
-    for (_, person_id) in persons_by_registration_time {
-        for &module_id in &registrations.persons[person_id].choice_of_modules {
-            module_to_ordered_persons
-                .entry(module_id)
-                .or_default()
-                .push(person_id);
+    // Save data quality warnings if requested
+    if let Some(path) = &args.warnings_json {
+        write_warnings_json(path, &registrations.warnings)?;
+    }
+
+    // Report data quality warnings to GitHub Actions if requested
+    if args.github_annotations {
+        for warning in &registrations.warnings {
+            eprintln!("{}", github_annotation(warning));
         }
     }
 
-    // Order modules by start time
-    let modules_by_start_time = registrations
-        .modules
-        .iter()
-        .enumerate()
-        .map(|(module_id, module)| (module.start_time, module_id))
-        .collect::<BTreeMap<OffsetDateTime, ModuleId>>();
+    // Decide who gets a seat in each module
+    let mut limits = Limits::load(
+        args.capacities.as_deref(),
+        args.affiliation_quota.as_deref(),
+        &registrations,
+    )?;
+    limits.override_capacities(&args.capacity, &registrations);
+    if let Some(path) = &args.vip {
+        limits.load_vips(path)?;
+    }
+    if let Some(path) = &args.state_aliases {
+        limits.load_state_aliases(path)?;
+    }
+    limits.waitlist_state =
+        (!args.waitlist_state.trim().is_empty()).then(|| args.waitlist_state.as_str().into());
+    if !limits.state_aliases.is_empty() {
+        limits.warn_about_unrecognized_states(&registrations);
+    }
+    limits.fair_rotation = args.fair_rotation;
+    limits.respect_choice_order = args.respect_choice_order;
+    let module_rosters = if args.preserve_input_order {
+        registrations.input_order_rosters()
+    } else {
+        registrations.module_rosters(args.round_registration_times)
+    };
+    let mut rosters = limits.admit_all(&registrations, module_rosters.clone());
+
+    // Answer queries on module choices instead of writing the usual output
+    let module_query = if !args.registered_to_all.is_empty() {
+        Some((&args.registered_to_all, true))
+    } else if !args.registered_to_any.is_empty() {
+        Some((&args.registered_to_any, false))
+    } else {
+        None
+    };
+    if let Some((module_names, all)) = module_query {
+        let modules = module_names
+            .iter()
+            .map(|name| {
+                registrations
+                    .module_id(name)
+                    .ok_or_else(|| invalid_data(format!("Unknown module \"{}\"", name.trim())))
+            })
+            .collect::<csv::Result<Vec<_>>>()?;
+        let options = DisplayOptions {
+            format: args.format,
+            identity_format: args.identity_format,
+            locale: args.locale,
+            heading_offset: args.heading_offset,
+            truncate_names: args.truncate_names,
+            ..Default::default()
+        };
+        let (title, persons) = if all {
+            (
+                options.locale.strings().registered_to_all_title,
+                registrations.registered_to_all(&modules),
+            )
+        } else {
+            (
+                options.locale.strings().registered_to_any_title,
+                registrations.registered_to_any(&modules),
+            )
+        };
+        let mut output = String::new();
+        write_module_query(
+            &mut output,
+            &options,
+            &registrations,
+            title,
+            &modules,
+            &persons,
+            !all,
+        )
+        .expect("Writing to a String can't fail");
+        emit_output(&args, &output)?;
+        return Ok(());
+    }
+
+    // List registrants by affiliation instead of writing the usual output
+    if args.by_affiliation {
+        let options = DisplayOptions {
+            format: args.format,
+            identity_format: args.identity_format,
+            locale: args.locale,
+            heading_offset: args.heading_offset,
+            truncate_names: args.truncate_names,
+            ..Default::default()
+        };
+        let mut output = String::new();
+        write_affiliation_rosters(
+            &mut output,
+            &options,
+            &registrations,
+            &registrations.persons_by_affiliation(),
+        )
+        .expect("Writing to a String can't fail");
+        emit_output(&args, &output)?;
+        return Ok(());
+    }
+
+    // Explain someone's admissions instead of writing the usual output
+    if let Some(email) = &args.explain {
+        let person_id = registrations
+            .find_person_id(email)
+            .ok_or_else(|| invalid_data(format!("Nobody registered with email \"{email}\"")))?;
+        let options = DisplayOptions {
+            format: args.format,
+            identity_format: args.identity_format,
+            locale: args.locale,
+            heading_offset: args.heading_offset,
+            truncate_names: args.truncate_names,
+            time_format: args.time_format.clone(),
+            ..Default::default()
+        };
+        let mut output = String::new();
+        write_explanation(
+            &mut output,
+            &options,
+            &registrations,
+            &limits,
+            &module_rosters,
+            &rosters,
+            person_id,
+        )
+        .expect("Writing to a String can't fail");
+        emit_output(&args, &output)?;
+        return Ok(());
+    }
+
+    // Let the user review rejections before producing any output
+    if args.interactive
+        && !args.yes
+        && std::io::stdin().is_terminal()
+        && !confirm_rejections(&registrations, &rosters, args.locale)?
+    {
+        return Err(invalid_data(
+            "Aborted at user request, no output was produced".into(),
+        ));
+    }
+
+    // Decide in which order modules are displayed
+    let mut module_order = if args.preserve_input_order {
+        (0..registrations.modules.len()).collect()
+    } else if let Some(path) = &args.modules {
+        registrations.modules_by_canonical_order(
+            &load_module_list(path, &registrations)?,
+            args.unscheduled_placement,
+        )
+    } else {
+        registrations.modules_by_start_time()
+    };
+
+    // Only display the modules of interest, if any
+    if let Some(pattern) = &args.only_modules {
+        let regex = Regex::new(pattern)
+            .map_err(|e| invalid_data(format!("Invalid --only-modules regex: {e}")))?;
+        module_order.retain(|&module_id| regex.is_match(&registrations.modules[module_id].name));
+    }
+
+    // Save the full rosters for mail-merge if requested
+    if let Some(path) = &args.mailmerge_csv {
+        write_mailmerge_csv(path, &registrations, &module_order, &rosters)?;
+    }
+
+    // Save the full rosters as JSON if requested
+    if let Some(path) = &args.json_out {
+        write_rosters_json(path, &registrations, &module_order, &rosters)?;
+    }
+
+    // Save a summary of this run if requested
+    if let Some(path) = &args.report_json {
+        write_run_report(path, &registrations, &module_order, &rosters)?;
+    }
+
+    // Save contact information if requested
+    if let Some(path) = &args.vcard {
+        write_vcards(path, &registrations)?;
+    }
+
+    // Render personalized emails if requested
+    if let (Some(template_path), Some(dir)) = (&args.email_template, &args.render_emails) {
+        render_emails(
+            &std::fs::read_to_string(template_path)?,
+            dir,
+            &registrations,
+            &module_order,
+            &rosters,
+        )?;
+    }
+
+    // Save the full rosters as an Excel workbook if requested
+    #[cfg(feature = "xlsx")]
+    if let Some(path) = &args.xlsx_out {
+        indico_transpose::output::write_xlsx(path, &registrations, &module_order, &rosters)
+            .map_err(std::io::Error::other)?;
+    }
 
     // Display module registrations
-    println!("# Registrations to each module");
-    for (_, module_id) in modules_by_start_time {
-        println!("\n## {}\n", registrations.modules[module_id].name);
-        for (idx, &person_id) in module_to_ordered_persons[&module_id].iter().enumerate() {
-            println!("{}. {}", idx + 1, registrations.persons[person_id].identity);
+    let options = DisplayOptions {
+        format: args.format,
+        identity_format: args.identity_format,
+        locale: args.locale,
+        heading_offset: args.heading_offset,
+        show_module_times: args.show_module_times,
+        module_metadata,
+        attendance: args
+            .attendance
+            .as_deref()
+            .map(load_attendance)
+            .transpose()?,
+        vips: limits.vips.clone(),
+        truncate_names: args.truncate_names,
+        cohorts: args.cohorts.then(|| registrations.registration_cohorts()),
+        track_separator: args.group_by_prefix.as_deref().map(Into::into),
+        compact: args.compact,
+        time_format: args.time_format.clone(),
+    };
+    let mut output = String::new();
+    if args.summary_header {
+        write_summary_header(&mut output, &options, &registrations)
+            .expect("Writing to a String can't fail");
+    }
+    if !limits.capacities.is_empty() {
+        write_capacity_summary(
+            &mut output,
+            &options,
+            &registrations,
+            &module_order,
+            &rosters,
+        )
+        .expect("Writing to a String can't fail");
+    }
+    if let Some(threshold_pct) = args.capacity_warn_pct {
+        write_nearing_capacity(
+            &mut output,
+            &options,
+            &registrations,
+            &module_order,
+            &rosters,
+            threshold_pct,
+        )
+        .expect("Writing to a String can't fail");
+    }
+
+    if let Some(path) = &args.since_last_run {
+        if let Some(last_run) = load_last_run(path)? {
+            write_new_registrations(
+                &mut output,
+                &options,
+                &registrations,
+                &module_order,
+                &rosters,
+                last_run,
+            )
+            .expect("Writing to a String can't fail");
+        }
+    }
+
+    // Only keep people from the affiliation of interest, if any
+    if let Some(affiliation) = &args.only_affiliation {
+        let affiliation = affiliation.trim().to_lowercase();
+        for roster in &mut rosters {
+            roster.retain(|person_id| {
+                registrations.persons[person_id]
+                    .identity
+                    .simplified_affiliation()
+                    .to_lowercase()
+                    == affiliation
+            });
+        }
+    }
+
+    if args.by_person {
+        write_schedules(
+            &mut output,
+            &options,
+            &registrations,
+            &registrations.person_schedules(),
+            &rosters,
+        )
+        .expect("Writing to a String can't fail");
+    } else if let Some(modules_per_part) = args.paginate {
+        let output_path = Path::new(args.output.as_deref().expect("Required by clap"));
+        let directory = output_path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        write_paginated_rosters(
+            &mut output,
+            directory,
+            &options,
+            &registrations,
+            &module_order,
+            &rosters,
+            modules_per_part,
+        )?;
+    } else {
+        write_rosters(
+            &mut output,
+            &options,
+            &registrations,
+            &module_order,
+            &rosters,
+        )
+        .expect("Writing to a String can't fail");
+    }
+    if args.attendance_report {
+        write_attendance_report(
+            &mut output,
+            &options,
+            &registrations,
+            &module_order,
+            &rosters,
+        )
+        .expect("Writing to a String can't fail");
+    }
+    if let Some(max_per_day) = args.max_per_person_per_day {
+        let overloads = registrations.daily_overloads(max_per_day);
+        if !overloads.is_empty() {
+            write_daily_overloads(&mut output, &options, &registrations, &overloads)
+                .expect("Writing to a String can't fail");
+        }
+    }
+    if args.report_hidden {
+        let hidden = registrations.persons_outside(&module_order);
+        if !hidden.is_empty() {
+            write_hidden_registrants(&mut output, &options, &registrations, &hidden)
+                .expect("Writing to a String can't fail");
+        }
+    }
+    let late_registrations = registrations.late_registrations();
+    if !late_registrations.is_empty() {
+        write_late_registrations(&mut output, &options, &registrations, &late_registrations)
+            .expect("Writing to a String can't fail");
+    }
+    if let Some(window) = args.registration_window {
+        let out_of_range = registrations.out_of_range_registrations(window);
+        if !out_of_range.is_empty() {
+            write_out_of_range_registrations(&mut output, &options, &registrations, &out_of_range)
+                .expect("Writing to a String can't fail");
+        }
+    }
+    if let Some(max_gap) = args.check_ids {
+        if registrations.record_ids.is_empty() {
+            return Err(invalid_data(
+                "--check-ids requires an \"ID\" column in the input".into(),
+            ));
+        }
+        write_id_anomalies(&mut output, &options, &registrations.id_anomalies(max_gap))
+            .expect("Writing to a String can't fail");
+    }
+    if let Some(path) = &args.expected_counts {
+        write_count_mismatches(
+            &mut output,
+            &options,
+            &registrations,
+            &load_expected_counts(path)?,
+        )
+        .expect("Writing to a String can't fail");
+    }
+    if args.report_unsimplified_affiliations {
+        write_unsimplified_affiliations(
+            &mut output,
+            &options,
+            &registrations.unsimplified_affiliations(),
+        )
+        .expect("Writing to a String can't fail");
+    }
+    if let Some(count) = args.top_registrants {
+        write_top_registrants(
+            &mut output,
+            &options,
+            &registrations,
+            &registrations.top_registrants(count),
+        )
+        .expect("Writing to a String can't fail");
+    }
+    let possible_duplicates = registrations.possible_duplicates();
+    if !possible_duplicates.is_empty() {
+        write_possible_duplicates(&mut output, &options, &registrations, &possible_duplicates)
+            .expect("Writing to a String can't fail");
+    }
+    if args.stats {
+        let affiliation_parents = match args.stats_group_by {
+            StatsGroupBy::Leaf => None,
+            StatsGroupBy::Parent => args
+                .affiliation_hierarchy
+                .as_deref()
+                .map(load_affiliation_hierarchy)
+                .transpose()?,
+        };
+        write_stats(
+            &mut output,
+            &options,
+            &registrations,
+            args.dedup,
+            affiliation_parents.as_ref(),
+        )
+        .expect("Writing to a String can't fail");
+    }
+    if let Some(hash) = input_hash {
+        options
+            .format
+            .markup()
+            .write_comment(&mut output, &format!("Input SHA-256: {hash}"))
+            .expect("Writing to a String can't fail");
+    }
+    emit_output(&args, &output)?;
+
+    // Remember what this run has seen, once its output is safe
+    if let Some(path) = &args.since_last_run {
+        if let Some(last_registration_time) = (registrations.persons.iter())
+            .map(|person| person.registration_time)
+            .max()
+        {
+            save_last_run(path, last_registration_time)?;
         }
     }
     Ok(())
 }
 
-// === Input data from Indico ===
-
-/// Indico registration record
+/// Parse command-line arguments, along with the `--config` file if any
 ///
-/// The optional fields are those which I'm not using yet, but which sounded
-/// interesting and which I'm considering for future use.
-#[allow(unused)]
-#[derive(Debug, Deserialize)]
-struct CSVRecord {
-    #[serde(rename = "ID")]
-    id: Option<usize>,
-    #[serde(flatten)]
-    identity: Identity,
-    #[serde(rename = "Choice of modules")]
-    choice_of_modules: Box<str>,
-    #[serde(rename = "Registration date", with = "indico_datetime")]
-    registration_time: OffsetDateTime,
-    #[serde(rename = "Registration state")]
-    registration_state: Option<Box<str>>,
-}
+/// Each key of the config file is a command-line option, with its value.
+/// Flags are set with `true`, and options that accept several values can be
+/// given an array. Options that are set on the command line take precedence.
+fn parse_args() -> csv::Result<Args> {
+    let cli_args = std::env::args_os().collect::<Vec<_>>();
+    let matches = Args::command().get_matches_from(&cli_args);
+    let Some(config_path) = matches.get_one::<String>("config") else {
+        return Ok(Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit()));
+    };
+    let config = std::fs::read_to_string(config_path)?
+        .parse::<toml::Table>()
+        .map_err(|e| invalid_data(format!("Invalid config file {config_path} ({e})")))?;
 
-/// Basic information about people that we want to display in the end
-#[derive(Debug, Deserialize)]
-struct Identity {
-    #[serde(rename = "Name")]
-    name: Box<str>,
-    #[serde(rename = "Email Address")]
-    email_address: Box<str>,
-    #[serde(rename = "Affiliation")]
-    affiliation: Box<str>,
-}
-//
-impl Display for Identity {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "`{} <{}>`", self.name, self.email_address)?;
-        if !self.affiliation.is_empty() {
-            static SIMPLIFIED_AFFILIATIONS: OnceLock<HashMap<Box<str>, Arc<str>>> = OnceLock::new();
-            let simplified_affiliations = SIMPLIFIED_AFFILIATIONS.get_or_init(|| {
-                let mut result = HashMap::new();
-                let ijclab = Arc::<str>::from("IJCLab");
-                result.insert("Laboratoire de Physique des 2 infinis Irène Joliot-Curie, Université Paris-Saclay, CNRS-IN2P3. Université Paris-Saclay, CNRS-IN2P3".into(), ijclab.clone());
-                result.insert("IJCLAB - IN2P3 - CNRS".into(), ijclab.clone());
-                result.insert("IJCLab - IN2P3 - CNRS".into(), ijclab.clone());
-                result
+    // Translate the config file into command-line arguments, which come
+    // before the actual command-line arguments
+    let command = Args::command();
+    let mut args = cli_args[..1].to_vec();
+    for (key, value) in config {
+        let id = key.replace('-', "_");
+        let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == id.as_str() && id != "config")
+        else {
+            return Err(invalid_data(format!(
+                "Unknown option \"{key}\" in config file {config_path}"
+            )));
+        };
+        let on_command_line =
+            |id: &clap::Id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine);
+        if on_command_line(arg.get_id()) {
+            continue;
+        }
+
+        // Command-line arguments also override conflicting config keys
+        let conflicts_with_command_line = command.get_arguments().any(|other| {
+            on_command_line(other.get_id())
+                && (command.get_arg_conflicts_with(arg).contains(&other)
+                    || command.get_arg_conflicts_with(other).contains(&arg))
+        });
+        if conflicts_with_command_line {
+            continue;
+        }
+        let flag = arg.get_long().map(|long| format!("--{long}"));
+        let values = match value {
+            toml::Value::Boolean(false) => continue,
+            toml::Value::Boolean(true) => {
+                args.extend(flag.iter().map(Into::into));
+                continue;
+            }
+            toml::Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            args.extend(flag.iter().map(Into::into));
+            args.push(match value {
+                toml::Value::String(string) => string.into(),
+                other => other.to_string().into(),
             });
-            let affiliation =
-                if let Some(simplified) = simplified_affiliations.get(&*self.affiliation) {
-                    simplified
-                } else {
-                    &*self.affiliation
-                };
-            write!(f, " from {affiliation}")?;
         }
-        Ok(())
     }
+    args.extend_from_slice(&cli_args[1..]);
+    let matches = Args::command().get_matches_from(args);
+    Ok(Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit()))
 }
 
-// Date/time format used by Indico
-time::serde::format_description!(
-    indico_datetime,
-    OffsetDateTime,
-    "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond][offset_hour sign:mandatory]:[offset_minute]"
-);
-
-/// Load CSV records
-fn load_raw_records(mut csv_reader: csv::Reader<File>) -> csv::Result<Vec<CSVRecord>> {
-    debug!("Loading CSV registration records...");
-    let mut result = Vec::new();
-    for record in csv_reader.deserialize() {
-        debug!("- {record:#?}");
-        let record: CSVRecord = record?;
-        result.push(record);
-    }
-    Ok(result)
-}
-
-// === Pre-digested data ===
+/// Read out registrations from every input file matching a glob pattern
+///
+/// If a hasher is provided, the contents of the input files are fed into it.
+fn load_registrations(
+    args: &Args,
+    pattern: &str,
+    mut hasher: Option<&mut Sha256>,
+) -> csv::Result<Registrations> {
+    let columns = ColumnMapping {
+        priority: args.priority_column.clone(),
+        modules: args.module_columns.clone(),
+        modules_json: args.module_field_json,
+        assumed_offset: Some(args.assume_offset),
+        allow_date_only: args.allow_date_only,
+    };
+    let inputs = expand_input_glob(pattern)?
+        .into_iter()
+        .map(|path| -> std::io::Result<Box<dyn BufRead>> {
+            if let Some(hasher) = &mut hasher {
+                let bytes = std::fs::read(&path)?;
+                hasher.update(&bytes);
+                Ok(Box::new(Cursor::new(bytes)))
+            } else {
+                Ok(Box::new(BufReader::new(File::open(&path)?)))
+            }
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
 
-/// Exploitable version of the Indico registration records
-#[derive(Debug, Default)]
-struct Registrations {
-    /// List of people who registered to courses
-    persons: Vec<Person>,
+    if let Some(sample) = args.sample {
+        log::warn!("Only processing the first {sample} records, results will be partial");
+    }
 
-    /// List of pedagogical modules
-    modules: Vec<Module>,
+    // Translate records into a more exploitable data layout, as they come
+    RegistrationsBuilder::new()
+        .options(ProcessingOptions {
+            strip_date_from_name: args.strip_date_from_name,
+            name_layout: args.name_layout,
+            dedup: args.dedup,
+            preserve_input_order: args.preserve_input_order,
+            module_time_granularity: args.module_time_granularity,
+            month_names: args.month_names,
+            unparsed_modules: args.no_parse_modules.clone(),
+            fuzzy_modules: args.fuzzy_modules,
+            year: Some(args.year),
+            ..Default::default()
+        })
+        .columns(columns)
+        .skip_lines(args.skip_lines)
+        .sample(args.sample)
+        .read(inputs)
 }
 
-/// Index of a person within Registrations::persons
-type PersonId = usize;
+/// Report who registered to or left each module between two snapshots
+fn diff_snapshots(args: &Args, old_path: &str, new_path: &str) -> csv::Result<()> {
+    let old = load_registrations(args, old_path, None)?;
+    let new = load_registrations(args, new_path, None)?;
+    let options = DisplayOptions {
+        format: args.format,
+        identity_format: args.identity_format,
+        locale: args.locale,
+        heading_offset: args.heading_offset,
+        truncate_names: args.truncate_names,
+        ..Default::default()
+    };
+    let mut output = String::new();
+    write_diff(&mut output, &options, &new.diff_from(&old))
+        .expect("Writing to a String can't fail");
+    emit_output(args, &output)?;
+    Ok(())
+}
 
-/// Index of a module within Registrations::modules
-type ModuleId = usize;
+/// Parse a name similarity threshold, which should be between 0 and 1
+fn parse_similarity(threshold: &str) -> Result<f64, String> {
+    let threshold = threshold.trim().parse::<f64>().map_err(|e| e.to_string())?;
+    if (0.0..=1.0).contains(&threshold) {
+        Ok(threshold)
+    } else {
+        Err(format!(
+            "similarity should be between 0 and 1, found {threshold}"
+        ))
+    }
+}
 
-/// What we need to know about someone who registered to modules
-#[derive(Debug)]
-struct Person {
-    /// Information identifying this person
-    identity: Identity,
+/// Show how many people each module rejects, and ask for confirmation
+///
+/// Returns true if there are no rejections, or if the user agreed to proceed.
+fn confirm_rejections(
+    registrations: &Registrations,
+    rosters: &[Roster],
+    locale: Locale,
+) -> std::io::Result<bool> {
+    let mut stderr = std::io::stderr().lock();
+    let mut any_rejection = false;
+    for module_id in registrations.modules_by_start_time() {
+        let num_waitlisted = rosters[module_id].waitlisted.len();
+        if num_waitlisted > 0 {
+            any_rejection = true;
+            writeln!(
+                stderr,
+                "{}: {}",
+                registrations.modules[module_id].name,
+                locale.count(num_waitlisted, locale.strings().would_be_waitlisted)
+            )?;
+        }
+    }
+    if !any_rejection {
+        return Ok(true);
+    }
+    write!(stderr, "Proceed? [y/N] ")?;
+    stderr.flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes" | "YES"))
+}
 
-    /// Which modules they chose to attend
-    choice_of_modules: Vec<ModuleId>,
+/// Display bytes in hexadecimal form
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
 
-    /// Time at which they registered
-    registration_time: OffsetDateTime,
+/// Set up logging, optionally mirroring warnings to a file
+fn init_logger(warnings_path: Option<&str>) -> std::io::Result<()> {
+    let logger = env_logger::Builder::from_default_env().build();
+    let Some(path) = warnings_path else {
+        log::set_max_level(logger.filter());
+        log::set_boxed_logger(Box::new(logger)).expect("Logger should only be set up once");
+        return Ok(());
+    };
+    log::set_max_level(logger.filter().max(log::LevelFilter::Warn));
+    log::set_boxed_logger(Box::new(WarningsFileLogger {
+        inner: logger,
+        file: Mutex::new(File::create(path)?),
+    }))
+    .expect("Logger should only be set up once");
+    Ok(())
 }
 
-/// What we know about a module
-#[derive(Debug)]
-struct Module {
-    /// Name of the module
-    name: Rc<str>,
+/// Logger that mirrors warnings and errors to a file
+struct WarningsFileLogger {
+    /// Logger used for stderr output
+    inner: env_logger::Logger,
 
-    /// Date and time at which the module will start
-    start_time: OffsetDateTime,
+    /// File where warnings and errors are written
+    file: Mutex<File>,
 }
 //
-impl Module {
-    /// Create a new module entry from the module name in Indico CSV
-    fn new(module_name: &str) -> Self {
-        debug!("- Registered new module: {module_name}");
-        static START_TIME_REGEX: OnceLock<Regex> = OnceLock::new();
-        let start_time_regex = START_TIME_REGEX.get_or_init(|| {
-            Regex::new(
-                r"([0-9]{1,2})/([0-9]{1,2})(?: \+ [a-z]+. [0-9]+/[0-9]+)?, ([0-9]{1,2})[:h]([0-9]{1,2})",
-            )
-            .expect("Regex was manually checked")
-        });
-        let start_time = if let Some((_, day_month_hour_min)) = start_time_regex
-            .captures(module_name)
-            .map(|cap| cap.extract())
-        {
-            let [day, month, hour, min] = day_month_hour_min.map(|s| s.parse::<usize>().unwrap());
-            OffsetDateTime::new_utc(
-                Date::from_calendar_date(2024, Month::January.nth_next(month as u8 - 1), day as u8)
-                    .expect("Module date should be valid"),
-                Time::from_hms(hour as u8, min as u8, 0).expect("Module time should be valid"),
-            )
-        } else {
-            warn!(
-                "Couldn't parse start time of module \"{module_name}\", it will be unordered in output"
+impl log::Log for WarningsFileLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+        if record.level() <= log::Level::Warn {
+            let mut file = self.file.lock().expect("Logging thread panicked");
+            // Logging has no way to report errors, and stderr still gets them
+            let _ = writeln!(
+                file,
+                "[{} {}] {}",
+                record.level(),
+                record.target(),
+                record.args()
             );
-            OffsetDateTime::new_utc(Date::MAX, Time::MIDNIGHT)
-        };
-        Self {
-            name: module_name.into(),
-            start_time,
         }
     }
+
+    fn flush(&self) {
+        self.inner.flush();
+        let _ = self.file.lock().expect("Logging thread panicked").flush();
+    }
 }
 
-impl Registrations {
-    /// Translate raw Indico records into a more exploitable form
-    fn new(raw_records: Vec<CSVRecord>) -> Self {
-        debug!("Post-processing registration records...");
-        let mut result = Self::default();
-        let mut module_to_id = HashMap::new();
-        for CSVRecord {
-            identity,
-            choice_of_modules,
-            registration_time,
-            ..
-        } in raw_records
-        {
-            let module_ids = choice_of_modules
-                .split(';')
-                .map(|module_name| {
-                    let module_name = module_name.trim();
-                    // Have we seen this module before?
-                    let module_id = if let Some(module_id) = module_to_id.get(module_name) {
-                        // Reuse previous module ID
-                        *module_id
-                    } else {
-                        // Post-process module name, deduce module ID
-                        let module = Module::new(module_name);
-                        let module_name = module.name.clone();
-                        let module_id = result.modules.len();
-                        result.modules.push(module);
-                        module_to_id.insert(module_name, module_id);
-                        module_id
-                    };
-                    module_id
-                })
-                .collect();
-            result.persons.push(Person {
-                identity,
-                choice_of_modules: module_ids,
-                registration_time,
-            })
+/// Save data quality warnings as a JSON document
+fn write_warnings_json(path: &str, warnings: &[DataWarning]) -> std::io::Result<()> {
+    #[derive(Serialize)]
+    struct WarningsReport<'warnings> {
+        warnings: &'warnings [DataWarning],
+    }
+    let json = serde_json::to_string_pretty(&WarningsReport { warnings })?;
+    std::fs::write(path, json + "\n")
+}
+
+/// Format a data quality warning as a GitHub Actions workflow command
+fn github_annotation(warning: &DataWarning) -> String {
+    let escape = |text: &str| {
+        text.replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    };
+    format!(
+        "::warning title={}::{}",
+        escape(&warning.category())
+            .replace(':', "%3A")
+            .replace(',', "%2C"),
+        escape(&warning.to_string())
+    )
+}
+
+/// Send the output to its final destination
+fn emit_output(args: &Args, output: &str) -> std::io::Result<()> {
+    if let Some(path) = &args.output {
+        return write_atomically(path.as_ref(), output);
+    }
+    #[cfg(feature = "clipboard")]
+    if args.clipboard {
+        return copy_or_print(output, copy_to_clipboard, &mut std::io::stdout());
+    }
+    print!("{output}");
+    Ok(())
+}
+
+/// Copy the output with `copy`, falling back to writing it to `fallback` if
+/// that fails, e.g. on a headless system without a clipboard
+#[cfg(any(feature = "clipboard", test))]
+fn copy_or_print<E: std::fmt::Display>(
+    output: &str,
+    copy: impl FnOnce(&str) -> Result<(), E>,
+    fallback: &mut impl std::io::Write,
+) -> std::io::Result<()> {
+    match copy(output) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            log::error!("Failed to copy output to the clipboard ({e}), printing it instead");
+            fallback.write_all(output.as_bytes())
         }
-        result
+    }
+}
+
+/// Copy some text to the system clipboard
+#[cfg(feature = "clipboard")]
+fn copy_to_clipboard(text: &str) -> Result<(), arboard::Error> {
+    let mut clipboard = arboard::Clipboard::new()?;
+    #[cfg(target_os = "linux")]
+    {
+        use arboard::SetExtLinux;
+        clipboard.set().wait().text(text)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        clipboard.set_text(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipboard_copy() {
+        let mut copied = String::new();
+        let mut printed = Vec::new();
+        copy_or_print(
+            "# Rosters\n",
+            |text| {
+                copied.push_str(text);
+                Ok::<(), String>(())
+            },
+            &mut printed,
+        )
+        .unwrap();
+        assert_eq!(copied, "# Rosters\n");
+        assert!(printed.is_empty());
+    }
+
+    #[test]
+    fn unavailable_clipboard() {
+        let mut printed = Vec::new();
+        copy_or_print("# Rosters\n", |_| Err("no display"), &mut printed).unwrap();
+        assert_eq!(printed, b"# Rosters\n");
     }
 }
@@ -0,0 +1,2324 @@
+//! Output formatting
+
+use crate::{
+    admission::{Admission, Limits, Roster, WaitlistReason},
+    input::{ExpectedCountRecord, Identity, ModuleMetadata},
+    registrations::{
+        normalize_email, normalize_name, Cohort, DailyOverload, IdAnomaly, ModuleDiff, ModuleId,
+        PersonId, Registrations,
+    },
+};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    borrow::Cow,
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+    path::Path,
+};
+use time::{
+    format_description::{well_known::Rfc3339, BorrowedFormatItem, OwnedFormatItem},
+    macros::format_description,
+    OffsetDateTime,
+};
+
+/// Supported output formats
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// One Markdown list per module
+    #[default]
+    Markdown,
+
+    /// One Markdown table per module, with rank, name and affiliation columns
+    MarkdownTable,
+
+    /// One printable Markdown table per module, like `markdown-table` with
+    /// an extra blank signature column
+    AttendanceSheet,
+
+    /// One GitHub-flavored Markdown task list per module, so that people can
+    /// be ticked off
+    GfmTasklist,
+
+    /// One plain text list per module
+    Plain,
+
+    /// One HTML list per module
+    Html,
+}
+
+/// Which information is displayed about each person
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum IdentityFormat {
+    /// Name, email address and affiliation
+    #[default]
+    NameEmailAffiliation,
+
+    /// Name only
+    NameOnly,
+
+    /// Name and affiliation
+    NameAffiliation,
+
+    /// Name and email address, without affiliation
+    NameEmail,
+}
+//
+impl IdentityFormat {
+    /// Truth that this format displays affiliations
+    pub fn shows_affiliation(self) -> bool {
+        matches!(self, Self::NameEmailAffiliation | Self::NameAffiliation)
+    }
+}
+
+/// Language of the static output text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Locale {
+    /// English
+    #[default]
+    En,
+
+    /// French
+    Fr,
+}
+//
+impl Locale {
+    /// Static output text in this language
+    pub fn strings(self) -> &'static Strings {
+        match self {
+            Self::En => &Strings::EN,
+            Self::Fr => &Strings::FR,
+        }
+    }
+
+    /// Format a count followed by a noun, in singular or plural form
+    ///
+    /// The singular form is used for a count of 1 in English, and for counts
+    /// of 0 and 1 in French.
+    pub fn count(self, count: usize, (singular, plural): (&str, &str)) -> String {
+        let is_singular = match self {
+            Self::En => count == 1,
+            Self::Fr => count <= 1,
+        };
+        format!("{count} {}", if is_singular { singular } else { plural })
+    }
+}
+
+/// Static output headings, in some language
+#[derive(Debug)]
+pub struct Strings {
+    /// Title of the per-module rosters
+    pub rosters_title: &'static str,
+
+    /// Heading of a module's waiting list
+    pub waiting_list: &'static str,
+
+    /// Placeholder for the start time of modules whose schedule is unknown
+    pub unknown_time: &'static str,
+
+    /// Label of module locations
+    pub location: &'static str,
+
+    /// Title of the per-person schedules
+    pub schedules_title: &'static str,
+
+    /// Title of the statistics section
+    pub stats_title: &'static str,
+
+    /// Heading of the per-affiliation statistics
+    pub affiliations_title: &'static str,
+
+    /// Placeholder for people whose affiliation is unknown
+    pub unknown_affiliation: &'static str,
+
+    /// Title of the attendance report
+    pub attendance_title: &'static str,
+
+    /// Title of the daily overload report
+    pub daily_overload_title: &'static str,
+
+    /// Title of the possible duplicates report
+    pub possible_duplicates_title: &'static str,
+
+    /// Title of the registration count mismatch report
+    pub count_mismatches_title: &'static str,
+
+    /// Title of the late registrations report
+    pub late_registrations_title: &'static str,
+
+    /// Title of the unsimplified affiliations report
+    pub unsimplified_title: &'static str,
+
+    /// Title of the top registrants report
+    pub top_registrants_title: &'static str,
+
+    /// Title of the comparison between two registration snapshots
+    pub diff_title: &'static str,
+
+    /// Title of the explanation of someone's admissions
+    pub explanation_title: &'static str,
+
+    /// Title of the out-of-range registrations report
+    pub out_of_range_title: &'static str,
+
+    /// Heading of modules which don't belong to any track
+    pub ungrouped: &'static str,
+
+    /// Title of the per-module affiliation diversity statistics
+    pub module_diversity_title: &'static str,
+
+    /// Title of the summary of oversubscribed modules
+    pub capacity_summary_title: &'static str,
+
+    /// Title of the list of people registered to all queried modules
+    pub registered_to_all_title: &'static str,
+
+    /// Title of the list of people registered to any queried module
+    pub registered_to_any_title: &'static str,
+
+    /// Title of the per-affiliation rosters
+    pub by_affiliation_title: &'static str,
+
+    /// Title of the list of modules that are nearly full
+    pub nearing_capacity_title: &'static str,
+
+    /// Name of each part of paginated output
+    pub part: &'static str,
+
+    /// Title of the report of suspicious record IDs
+    pub id_anomalies_title: &'static str,
+
+    /// Title of the list of people who chose none of the displayed modules
+    pub hidden_title: &'static str,
+
+    /// Title of the list of new registrations, followed by the time since
+    /// which they are new
+    pub new_registrations_title: &'static str,
+
+    /// Singular and plural forms of "person"
+    pub person: (&'static str, &'static str),
+
+    /// Singular and plural forms of "module"
+    pub module: (&'static str, &'static str),
+
+    /// Link between the number of persons and the number of modules in the
+    /// summary header
+    pub across: &'static str,
+
+    /// Singular and plural forms of "registration record"
+    pub registration_record: (&'static str, &'static str),
+
+    /// Singular and plural forms of "unique person"
+    pub unique_person: (&'static str, &'static str),
+
+    /// Singular and plural forms of "duplicate registration merged"
+    pub merged_duplicate: (&'static str, &'static str),
+
+    /// Singular and plural forms of "module registration"
+    pub module_registration: (&'static str, &'static str),
+
+    /// Singular and plural forms of "distinct person"
+    pub distinct_person: (&'static str, &'static str),
+
+    /// Singular and plural forms of "registrant"
+    pub registrant: (&'static str, &'static str),
+
+    /// Singular and plural forms of "affiliation"
+    pub affiliation: (&'static str, &'static str),
+
+    /// Link between a count of registrations and the people they come from
+    pub from: &'static str,
+
+    /// Link between a count of modules and the day on which they take place
+    pub on_day: &'static str,
+
+    /// Header of the rank column of roster tables
+    pub rank_header: &'static str,
+
+    /// Header of the name column of roster tables
+    pub name_header: &'static str,
+
+    /// Header of the affiliation column of roster tables
+    pub affiliation_header: &'static str,
+
+    /// Header of the cohort column of roster tables
+    pub cohort_header: &'static str,
+
+    /// Header of the attendance column of roster tables
+    pub present_header: &'static str,
+
+    /// Header of the signature column of attendance sheets
+    pub signature_header: &'static str,
+
+    /// Singular and plural forms of "added" registrants in snapshot
+    /// comparisons
+    pub added: (&'static str, &'static str),
+
+    /// Singular and plural forms of "removed" registrants in snapshot
+    /// comparisons
+    pub removed: (&'static str, &'static str),
+
+    /// Label of the net change of registrants in snapshot comparisons
+    pub net_change: &'static str,
+
+    /// Label of each added registrant in snapshot comparisons
+    pub added_label: &'static str,
+
+    /// Label of each removed registrant in snapshot comparisons
+    pub removed_label: &'static str,
+
+    /// Singular and plural forms of "person would be put on the waiting list"
+    pub would_be_waitlisted: (&'static str, &'static str),
+}
+//
+impl Strings {
+    /// English headings
+    pub const EN: Self = Self {
+        rosters_title: "Registrations to each module",
+        waiting_list: "Waiting list",
+        unknown_time: "(time unknown)",
+        location: "Location",
+        schedules_title: "Modules chosen by each person",
+        stats_title: "Statistics",
+        affiliations_title: "Persons per affiliation",
+        unknown_affiliation: "Unknown affiliation",
+        attendance_title: "Attendance",
+        daily_overload_title: "Daily overload",
+        possible_duplicates_title: "Possible duplicate people",
+        count_mismatches_title: "Count mismatches",
+        late_registrations_title: "Late registrations",
+        unsimplified_title: "Unsimplified affiliations",
+        top_registrants_title: "Top registrants",
+        diff_title: "Registration changes",
+        explanation_title: "Admission explanation",
+        out_of_range_title: "Out-of-range registrations",
+        ungrouped: "Ungrouped",
+        module_diversity_title: "Affiliations per module",
+        capacity_summary_title: "Capacity summary",
+        registered_to_all_title: "Persons registered to all of these modules",
+        registered_to_any_title: "Persons registered to any of these modules",
+        by_affiliation_title: "Registrations by affiliation",
+        nearing_capacity_title: "Nearing capacity",
+        part: "Part",
+        id_anomalies_title: "ID anomalies",
+        hidden_title: "Registrants without any displayed module",
+        new_registrations_title: "New registrations since",
+        person: ("person", "persons"),
+        module: ("module", "modules"),
+        across: "across",
+        registration_record: ("registration record", "registration records"),
+        unique_person: ("unique person", "unique persons"),
+        merged_duplicate: (
+            "duplicate registration merged",
+            "duplicate registrations merged",
+        ),
+        module_registration: ("module registration", "module registrations"),
+        distinct_person: ("distinct person", "distinct persons"),
+        registrant: ("registrant", "registrants"),
+        affiliation: ("affiliation", "affiliations"),
+        from: "from",
+        on_day: "on",
+        rank_header: "Rank",
+        name_header: "Name",
+        affiliation_header: "Affiliation",
+        cohort_header: "Cohort",
+        present_header: "Present",
+        signature_header: "Signature",
+        added: ("added", "added"),
+        removed: ("removed", "removed"),
+        net_change: "net change",
+        added_label: "Added",
+        removed_label: "Removed",
+        would_be_waitlisted: (
+            "person would be put on the waiting list",
+            "people would be put on the waiting list",
+        ),
+    };
+
+    /// French headings
+    pub const FR: Self = Self {
+        rosters_title: "Inscriptions à chaque module",
+        waiting_list: "Liste d'attente",
+        unknown_time: "(horaire inconnu)",
+        location: "Lieu",
+        schedules_title: "Modules choisis par chaque personne",
+        stats_title: "Statistiques",
+        affiliations_title: "Personnes par affiliation",
+        unknown_affiliation: "Affiliation inconnue",
+        attendance_title: "Présence",
+        daily_overload_title: "Surcharge journalière",
+        possible_duplicates_title: "Doublons possibles",
+        count_mismatches_title: "Effectifs incohérents",
+        late_registrations_title: "Inscriptions tardives",
+        unsimplified_title: "Affiliations non simplifiées",
+        top_registrants_title: "Personnes inscrites au plus de modules",
+        diff_title: "Évolution des inscriptions",
+        explanation_title: "Explication des admissions",
+        out_of_range_title: "Inscriptions hors période",
+        ungrouped: "Autres modules",
+        module_diversity_title: "Affiliations par module",
+        capacity_summary_title: "Modules complets",
+        registered_to_all_title: "Personnes inscrites à tous ces modules",
+        registered_to_any_title: "Personnes inscrites à l'un de ces modules",
+        by_affiliation_title: "Inscriptions par affiliation",
+        nearing_capacity_title: "Modules bientôt complets",
+        part: "Partie",
+        id_anomalies_title: "Identifiants suspects",
+        hidden_title: "Inscrits sans aucun module affiché",
+        new_registrations_title: "Nouvelles inscriptions depuis le",
+        person: ("personne", "personnes"),
+        module: ("module", "modules"),
+        across: "réparties sur",
+        registration_record: ("fiche d'inscription", "fiches d'inscription"),
+        unique_person: ("personne unique", "personnes uniques"),
+        merged_duplicate: (
+            "inscription en double fusionnée",
+            "inscriptions en double fusionnées",
+        ),
+        module_registration: ("inscription à un module", "inscriptions à des modules"),
+        distinct_person: ("personne distincte", "personnes distinctes"),
+        registrant: ("inscrit", "inscrits"),
+        affiliation: ("affiliation", "affiliations"),
+        from: "de",
+        on_day: "le",
+        rank_header: "Rang",
+        name_header: "Nom",
+        affiliation_header: "Affiliation",
+        cohort_header: "Cohorte",
+        present_header: "Présent",
+        signature_header: "Signature",
+        added: ("ajouté", "ajoutés"),
+        removed: ("retiré", "retirés"),
+        net_change: "variation nette",
+        added_label: "Ajout",
+        removed_label: "Retrait",
+        would_be_waitlisted: (
+            "personne serait mise en liste d'attente",
+            "personnes seraient mises en liste d'attente",
+        ),
+    };
+}
+
+/// Settings that affect how the output is displayed
+#[derive(Debug, Default)]
+pub struct DisplayOptions {
+    /// Output format
+    pub format: OutputFormat,
+
+    /// Information displayed about each person
+    pub identity_format: IdentityFormat,
+
+    /// Language of headings and other static output text
+    pub locale: Locale,
+
+    /// Number of levels by which all headings are shifted
+    pub heading_offset: usize,
+
+    /// Display the start time of modules in their headings
+    pub show_module_times: bool,
+
+    /// Extra information displayed below module headings
+    pub module_metadata: HashMap<ModuleId, ModuleMetadata>,
+
+    /// Normalized emails of people who actually attended, if known
+    pub attendance: Option<HashSet<Box<str>>>,
+
+    /// Normalized emails of people who are guaranteed a seat, who are
+    /// marked as VIPs
+    pub vips: HashSet<Box<str>>,
+
+    /// Maximal number of characters of displayed module names
+    pub truncate_names: Option<usize>,
+
+    /// Registration cohort of each person, if it should be displayed
+    pub cohorts: Option<Vec<Cohort>>,
+
+    /// Separator between the track and the title of module names, if
+    /// modules should be grouped by track
+    ///
+    /// For example, with a separator of ": ", the "ML: Intro" module is
+    /// displayed as "Intro" in the "ML" track.
+    pub track_separator: Option<Box<str>>,
+
+    /// Write each module's roster on a single line, with names only
+    pub compact: bool,
+
+    /// Format of displayed dates and times, see [`parse_time_format()`]
+    ///
+    /// If unset, times are displayed like "2024-03-15 14:00".
+    pub time_format: Option<OwnedFormatItem>,
+}
+//
+impl DisplayOptions {
+    /// Display a date and time in the configured format
+    pub fn format_time(&self, time: OffsetDateTime) -> String {
+        const DEFAULT_FORMAT: &[BorrowedFormatItem<'_>] =
+            format_description!("[year]-[month]-[day] [hour]:[minute]");
+        match &self.time_format {
+            Some(format) => time.format(format),
+            None => time.format(DEFAULT_FORMAT),
+        }
+        .expect("Time formats are checked by parse_time_format()")
+    }
+
+    /// Truth that someone is marked as a VIP
+    pub fn is_vip(&self, identity: &Identity) -> bool {
+        !self.vips.is_empty()
+            && self
+                .vips
+                .contains(&normalize_email(&identity.email_address))
+    }
+
+    /// Truth that someone attended, if attendance is known
+    pub fn is_present(&self, identity: &Identity) -> Option<bool> {
+        self.attendance
+            .as_ref()
+            .map(|attendance| attendance.contains(&normalize_email(&identity.email_address)))
+    }
+
+    /// Module name as displayed, truncated if requested
+    ///
+    /// Truncated names end with an ellipsis, which counts towards the length.
+    pub fn module_name<'name>(&self, name: &'name str) -> Cow<'name, str> {
+        match self.truncate_names {
+            Some(max_chars) if name.chars().count() > max_chars => {
+                let mut truncated = name
+                    .chars()
+                    .take(max_chars.saturating_sub(1))
+                    .collect::<String>();
+                truncated.push('…');
+                Cow::Owned(truncated)
+            }
+            _ => Cow::Borrowed(name),
+        }
+    }
+
+    /// Write a heading in the output markup, shifted by the heading offset
+    ///
+    /// Heading levels are capped to 6, the deepest level supported by HTML
+    /// and Markdown.
+    pub fn write_heading(&self, out: &mut String, level: usize, text: &str) -> std::fmt::Result {
+        let level = (level + self.heading_offset).min(6);
+        self.format.markup().write_heading(out, level, text)
+    }
+}
+
+/// Markup language used by an output format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Markup {
+    /// Markdown
+    Markdown,
+
+    /// Plain text
+    Plain,
+
+    /// HTML (fragment)
+    Html,
+}
+//
+impl OutputFormat {
+    /// Markup language used by this output format
+    pub fn markup(self) -> Markup {
+        match self {
+            Self::Markdown | Self::MarkdownTable | Self::AttendanceSheet | Self::GfmTasklist => {
+                Markup::Markdown
+            }
+            Self::Plain => Markup::Plain,
+            Self::Html => Markup::Html,
+        }
+    }
+}
+//
+impl Markup {
+    /// Escape raw text so that it can be embedded in this markup
+    pub fn escape(self, text: &str) -> Cow<'_, str> {
+        match self {
+            Self::Markdown | Self::Plain => Cow::Borrowed(text),
+            Self::Html => Cow::Owned(
+                text.replace('&', "&amp;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;")
+                    .replace('"', "&quot;"),
+            ),
+        }
+    }
+
+    /// Write a heading of a certain level (1 = top-level), from raw text
+    pub fn write_heading(self, out: &mut String, level: usize, text: &str) -> std::fmt::Result {
+        match self {
+            Self::Markdown => {
+                separate_block(out)?;
+                writeln!(out, "{} {text}\n", "#".repeat(level))
+            }
+            Self::Plain => {
+                separate_block(out)?;
+                writeln!(out, "{text}")?;
+                let underline = match level {
+                    1 => "=",
+                    2 => "-",
+                    _ => "~",
+                };
+                writeln!(out, "{}\n", underline.repeat(text.chars().count()))
+            }
+            Self::Html => writeln!(out, "<h{level}>{}</h{level}>", self.escape(text)),
+        }
+    }
+
+    /// Write a paragraph, from raw text
+    pub fn write_paragraph(self, out: &mut String, text: &str) -> std::fmt::Result {
+        match self {
+            Self::Markdown | Self::Plain => {
+                separate_block(out)?;
+                writeln!(out, "{text}\n")
+            }
+            Self::Html => writeln!(out, "<p>{}</p>", self.escape(text)),
+        }
+    }
+
+    /// Write a comment, which is hidden when the output is rendered
+    ///
+    /// Plain text has no comments, so the text is written as a paragraph.
+    pub fn write_comment(self, out: &mut String, text: &str) -> std::fmt::Result {
+        match self {
+            Self::Markdown | Self::Html => {
+                separate_block(out)?;
+                writeln!(out, "<!-- {} -->", text.replace("--", "- -"))
+            }
+            Self::Plain => self.write_paragraph(out, text),
+        }
+    }
+
+    /// Write a numbered list, whose items are already rendered in this markup
+    pub fn write_ordered_list(
+        self,
+        out: &mut String,
+        items: impl Iterator<Item = String>,
+    ) -> std::fmt::Result {
+        match self {
+            Self::Markdown | Self::Plain => {
+                for (idx, item) in items.enumerate() {
+                    writeln!(out, "{}. {item}", idx + 1)?;
+                }
+                Ok(())
+            }
+            Self::Html => {
+                writeln!(out, "<ol>")?;
+                for item in items {
+                    writeln!(out, "  <li>{item}</li>")?;
+                }
+                writeln!(out, "</ol>")
+            }
+        }
+    }
+
+    /// Write a bullet list, whose items are already rendered in this markup
+    pub fn write_bullet_list(
+        self,
+        out: &mut String,
+        items: impl Iterator<Item = String>,
+    ) -> std::fmt::Result {
+        match self {
+            Self::Markdown | Self::Plain => {
+                for item in items {
+                    writeln!(out, "- {item}")?;
+                }
+                Ok(())
+            }
+            Self::Html => {
+                writeln!(out, "<ul>")?;
+                for item in items {
+                    writeln!(out, "  <li>{item}</li>")?;
+                }
+                writeln!(out, "</ul>")
+            }
+        }
+    }
+
+    /// Render a link to some URL, from raw text
+    pub fn link(self, text: &str, url: &str) -> String {
+        match self {
+            Self::Markdown => format!("[{text}]({url})"),
+            Self::Plain => format!("{text} ({url})"),
+            Self::Html => format!("<a href=\"{}\">{}</a>", self.escape(url), self.escape(text)),
+        }
+    }
+
+    /// Usual file extension of documents in this markup
+    pub fn file_extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Plain => "txt",
+            Self::Html => "html",
+        }
+    }
+}
+
+/// Parse a `time` format description, like `[day]/[month] [hour]:[minute]`
+///
+/// Formats which can't display the date and time of a registration are
+/// rejected, so that they never fail later on.
+pub fn parse_time_format(spec: &str) -> Result<OwnedFormatItem, String> {
+    let format = time::format_description::parse_owned::<2>(spec)
+        .map_err(|e| format!("Invalid time format \"{spec}\" ({e})"))?;
+    OffsetDateTime::UNIX_EPOCH
+        .format(&format)
+        .map_err(|e| format!("Unusable time format \"{spec}\" ({e})"))?;
+    Ok(format)
+}
+
+/// Make sure that a new text block is separated from the previous one by a
+/// blank line, unless it is the beginning of the output
+pub fn separate_block(out: &mut String) -> std::fmt::Result {
+    if !out.is_empty() && !out.ends_with("\n\n") {
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Write the roster of each module, in the specified module order
+///
+/// If a track separator is set, modules are grouped under track headings,
+/// see [`DisplayOptions::track_separator`]. Tracks are ordered by their
+/// first module, and modules without a track come last.
+pub fn write_rosters(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    ordered_modules: &[ModuleId],
+    rosters: &[Roster],
+) -> std::fmt::Result {
+    options.write_heading(out, 1, options.locale.strings().rosters_title)?;
+    let ordered_modules = ordered_modules
+        .iter()
+        .copied()
+        .filter(|&module_id| !rosters[module_id].is_empty());
+    let Some(separator) = options.track_separator.as_deref() else {
+        let modules = ordered_modules
+            .map(|module_id| (module_id, &*registrations.modules[module_id].name))
+            .collect::<Vec<_>>();
+        return write_roster_group(out, options, registrations, &modules, rosters, 2);
+    };
+    let mut tracks = Vec::<(Option<&str>, Vec<(ModuleId, &str)>)>::new();
+    for module_id in ordered_modules {
+        let name = &registrations.modules[module_id].name;
+        let (track, name) = match name.split_once(separator) {
+            Some((track, title)) if !track.trim().is_empty() => (Some(track.trim()), title.trim()),
+            _ => (None, &**name),
+        };
+        match tracks.iter_mut().find(|(other, _)| *other == track) {
+            Some((_, modules)) => modules.push((module_id, name)),
+            None => tracks.push((track, vec![(module_id, name)])),
+        }
+    }
+    tracks.sort_by_key(|(track, _)| track.is_none());
+    for (track, modules) in tracks {
+        options.write_heading(out, 2, track.unwrap_or(options.locale.strings().ungrouped))?;
+        write_roster_group(out, options, registrations, &modules, rosters, 3)?;
+    }
+    Ok(())
+}
+
+/// Write the rosters of a group of modules, given with their displayed name
+///
+/// In compact mode, this is a list with one line per module. Otherwise, each
+/// module gets its own heading of the given level.
+fn write_roster_group(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    modules: &[(ModuleId, &str)],
+    rosters: &[Roster],
+    level: usize,
+) -> std::fmt::Result {
+    if !options.compact {
+        for &(module_id, name) in modules {
+            write_roster(
+                out,
+                options,
+                registrations,
+                module_id,
+                name,
+                &rosters[module_id],
+                level,
+            )?;
+        }
+        return Ok(());
+    }
+    let markup = options.format.markup();
+    let names = |persons: &mut dyn Iterator<Item = PersonId>| {
+        persons
+            .map(|person_id| {
+                markup
+                    .escape(&registrations.persons[person_id].identity.name)
+                    .into_owned()
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    markup.write_bullet_list(
+        out,
+        modules.iter().map(|&(module_id, name)| {
+            let roster = &rosters[module_id];
+            let mut line = format!(
+                "{} ({}): {}",
+                markup.escape(&options.module_name(name)),
+                roster.accepted.len(),
+                names(&mut roster.accepted.iter().copied())
+            );
+            if !roster.waitlisted.is_empty() {
+                write!(
+                    line,
+                    "; {}: {}",
+                    options.locale.strings().waiting_list,
+                    names(
+                        &mut roster
+                            .waitlisted
+                            .iter()
+                            .map(|&(person_id, _reason)| person_id)
+                    )
+                )
+                .expect("Writing to a String can't fail");
+            }
+            line
+        }),
+    )
+}
+
+/// Write the roster of each module into files of at most `modules_per_part`
+/// modules each, and a table of contents linking to them
+///
+/// Files are named `part-001.md`, `part-002.md`... (with an extension that
+/// matches the output markup), and written to `directory`. Each of them is
+/// like the output of [`write_rosters()`] for a subset of the modules. The
+/// table of contents, which lists the modules of each part, is written to
+/// `out`. Empty rosters are skipped.
+pub fn write_paginated_rosters(
+    out: &mut String,
+    directory: &Path,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    ordered_modules: &[ModuleId],
+    rosters: &[Roster],
+    modules_per_part: usize,
+) -> std::io::Result<()> {
+    let markup = options.format.markup();
+    let strings = options.locale.strings();
+    let ordered_modules = ordered_modules
+        .iter()
+        .copied()
+        .filter(|&module_id| !rosters[module_id].is_empty())
+        .collect::<Vec<_>>();
+    let mut contents = Vec::new();
+    for (idx, modules) in ordered_modules.chunks(modules_per_part.max(1)).enumerate() {
+        let file_name = format!("part-{:03}.{}", idx + 1, markup.file_extension());
+        let mut part = String::new();
+        write_rosters(&mut part, options, registrations, modules, rosters)
+            .expect("Writing to a String can't fail");
+        write_atomically(&directory.join(&file_name), &part)?;
+        let module_names = modules
+            .iter()
+            .map(|&module_id| {
+                markup
+                    .escape(&options.module_name(&registrations.modules[module_id].name))
+                    .into_owned()
+            })
+            .collect::<Vec<_>>();
+        contents.push(format!(
+            "{}: {}",
+            markup.link(&format!("{} {}", strings.part, idx + 1), &file_name),
+            module_names.join("; ")
+        ));
+    }
+    options
+        .write_heading(out, 1, strings.rosters_title)
+        .expect("Writing to a String can't fail");
+    markup
+        .write_bullet_list(out, contents.into_iter())
+        .expect("Writing to a String can't fail");
+    Ok(())
+}
+
+/// Write the roster of a single module, under a heading of the given level
+fn write_roster(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    module_id: ModuleId,
+    name: &str,
+    roster: &Roster,
+    level: usize,
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    let module = &registrations.modules[module_id];
+    if options.show_module_times {
+        let start_time = if module.is_scheduled() {
+            options.format_time(module.start_time)
+        } else {
+            options.locale.strings().unknown_time.to_owned()
+        };
+        options.write_heading(
+            out,
+            level,
+            &format!("{} — {start_time}", options.module_name(name)),
+        )?;
+    } else {
+        options.write_heading(out, level, &options.module_name(name))?;
+    }
+    if let Some(metadata) = options.module_metadata.get(&module_id) {
+        if let Some(description) = &metadata.description {
+            markup.write_paragraph(out, description)?;
+        }
+        if let Some(location) = &metadata.location {
+            markup.write_paragraph(
+                out,
+                &format!("{}: {location}", options.locale.strings().location),
+            )?;
+        }
+    }
+    write_persons(
+        out,
+        options,
+        registrations,
+        roster.accepted.iter().map(|&person_id| (person_id, None)),
+    )?;
+    if !roster.waitlisted.is_empty() {
+        options.write_heading(out, level + 1, options.locale.strings().waiting_list)?;
+        write_persons(
+            out,
+            options,
+            registrations,
+            roster
+                .waitlisted
+                .iter()
+                .map(|&(person_id, reason)| (person_id, Some(reason))),
+        )?;
+    }
+    Ok(())
+}
+
+/// Write how many people each oversubscribed module had to waitlist
+///
+/// Modules that have no capacity, or enough seats for everyone, are skipped.
+/// Nothing is written if no module is oversubscribed.
+pub fn write_capacity_summary(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    ordered_modules: &[ModuleId],
+    rosters: &[Roster],
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    let lines = ordered_modules
+        .iter()
+        .filter_map(|&module_id| {
+            let roster = &rosters[module_id];
+            let num_registered = roster.accepted.len() + roster.waitlisted.len();
+            let num_seats = roster.num_seats?;
+            (num_registered > num_seats).then(|| {
+                format!(
+                    "{}: {num_registered} registered, capacity {num_seats}, {} waitlisted",
+                    markup.escape(&options.module_name(&registrations.modules[module_id].name)),
+                    roster.waitlisted.len()
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        return Ok(());
+    }
+    options.write_heading(out, 1, options.locale.strings().capacity_summary_title)?;
+    markup.write_bullet_list(out, lines.into_iter())
+}
+
+/// Write the modules whose registrations exceed `threshold_pct` percent of
+/// their capacity, without exceeding it yet
+///
+/// This gives an early warning about modules that are about to become full.
+/// Oversubscribed modules are left to [`write_capacity_summary()`]. Nothing is
+/// written if no module is nearing capacity.
+pub fn write_nearing_capacity(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    ordered_modules: &[ModuleId],
+    rosters: &[Roster],
+    threshold_pct: f64,
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    let lines = ordered_modules
+        .iter()
+        .filter_map(|&module_id| {
+            let roster = &rosters[module_id];
+            let num_registered = roster.accepted.len() + roster.waitlisted.len();
+            let num_seats = roster.num_seats?;
+            let fill_pct = num_registered as f64 * 100.0 / num_seats as f64;
+            (fill_pct > threshold_pct && num_registered <= num_seats).then(|| {
+                format!(
+                    "{}: {num_registered} registered, capacity {num_seats} ({fill_pct:.0}% full)",
+                    markup.escape(&options.module_name(&registrations.modules[module_id].name)),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        return Ok(());
+    }
+    options.write_heading(out, 1, options.locale.strings().nearing_capacity_title)?;
+    markup.write_bullet_list(out, lines.into_iter())
+}
+
+/// Write the modules chosen by each person, ordered by name
+///
+/// `schedules` should come from [`Registrations::person_schedules()`]. Only
+/// modules where the person is listed in the roster are displayed, and people
+/// with no such module are omitted.
+pub fn write_schedules(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    schedules: &[(PersonId, Vec<ModuleId>)],
+    rosters: &[Roster],
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    options.write_heading(out, 1, options.locale.strings().schedules_title)?;
+    let mut schedules = schedules.iter().collect::<Vec<_>>();
+    schedules.sort_by_cached_key(|(person_id, _schedule)| {
+        let name = normalize_name(&registrations.persons[*person_id].identity.name);
+        (name, *person_id)
+    });
+    for (person_id, schedule) in schedules {
+        let items = schedule
+            .iter()
+            .filter_map(|&module_id| {
+                let status = rosters[module_id].status(*person_id)?;
+                let mut item = markup
+                    .escape(&options.module_name(&registrations.modules[module_id].name))
+                    .into_owned();
+                if let Admission::Waitlisted(_) = status {
+                    let waiting_list = options.locale.strings().waiting_list.to_lowercase();
+                    write!(item, " ({waiting_list})").expect("Writing to a String can't fail");
+                }
+                Some(item)
+            })
+            .collect::<Vec<_>>();
+        if items.is_empty() {
+            continue;
+        }
+        let identity = &registrations.persons[*person_id].identity;
+        options.write_heading(
+            out,
+            2,
+            &identity.render(Markup::Plain, options.identity_format),
+        )?;
+        markup.write_bullet_list(out, items.into_iter())?;
+    }
+    Ok(())
+}
+
+/// Write a one-line summary of how many people and modules there are
+pub fn write_summary_header(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+) -> std::fmt::Result {
+    let strings = options.locale.strings();
+    options.format.markup().write_paragraph(
+        out,
+        &format!(
+            "{} {} {}",
+            options
+                .locale
+                .count(registrations.persons.len(), strings.person),
+            strings.across,
+            options
+                .locale
+                .count(registrations.modules.len(), strings.module),
+        ),
+    )
+}
+
+/// Write statistics about the registrations
+///
+/// People who registered several times with the same email address are only
+/// counted once in the number of distinct persons, even without `dedup`.
+pub fn write_stats(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    dedup: bool,
+    affiliation_parents: Option<&HashMap<Box<str>, Box<str>>>,
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    options.write_heading(out, 1, options.locale.strings().stats_title)?;
+    let (locale, strings) = (options.locale, options.locale.strings());
+    let mut stats = vec![locale.count(registrations.num_records, strings.registration_record)];
+    if dedup {
+        stats.push(locale.count(registrations.persons.len(), strings.unique_person));
+        stats.push(locale.count(
+            registrations.num_merged_duplicates,
+            strings.merged_duplicate,
+        ));
+    } else {
+        stats.push(locale.count(registrations.persons.len(), strings.person));
+    }
+    stats.push(locale.count(registrations.modules.len(), strings.module));
+    let num_registrations = registrations
+        .registration_counts()
+        .into_iter()
+        .sum::<usize>();
+    let num_registered_persons = registrations
+        .persons
+        .iter()
+        .filter(|person| !person.choice_of_modules.is_empty())
+        .map(|person| normalize_email(&person.identity.email_address))
+        .collect::<HashSet<_>>()
+        .len();
+    stats.push(format!(
+        "{}, {} {}",
+        locale.count(num_registrations, strings.module_registration),
+        strings.from,
+        locale.count(num_registered_persons, strings.distinct_person),
+    ));
+    markup.write_bullet_list(out, stats.into_iter())?;
+
+    // Break down persons by affiliation, or by parent group of affiliations
+    let affiliation_group = |person_id: PersonId| {
+        let affiliation = registrations.persons[person_id]
+            .identity
+            .simplified_affiliation();
+        affiliation_parents
+            .and_then(|parents| parents.get(affiliation))
+            .map_or(affiliation, |parent| &**parent)
+    };
+    let mut group_counts = HashMap::<&str, usize>::new();
+    for person_id in 0..registrations.persons.len() {
+        *group_counts
+            .entry(affiliation_group(person_id))
+            .or_default() += 1;
+    }
+    let mut group_counts = group_counts.into_iter().collect::<Vec<_>>();
+    group_counts.sort_by_key(|&(group, count)| (Reverse(count), group.is_empty(), group));
+    options.write_heading(out, 2, options.locale.strings().affiliations_title)?;
+    markup.write_bullet_list(
+        out,
+        group_counts.into_iter().map(|(group, count)| {
+            let group = if group.is_empty() {
+                options.locale.strings().unknown_affiliation
+            } else {
+                group
+            };
+            format!("{}: {count}", markup.escape(group))
+        }),
+    )?;
+
+    // Count the distinct (known) affiliations that each module draws from
+    let module_rosters = registrations.input_order_rosters();
+    options.write_heading(out, 2, options.locale.strings().module_diversity_title)?;
+    markup.write_bullet_list(
+        out,
+        registrations
+            .modules_by_start_time()
+            .into_iter()
+            .map(|module_id| {
+                let registrants = &module_rosters[module_id];
+                let num_groups = registrants
+                    .iter()
+                    .map(|&person_id| affiliation_group(person_id))
+                    .filter(|group| !group.is_empty())
+                    .collect::<HashSet<_>>()
+                    .len();
+                format!(
+                    "{}: {} {} {}",
+                    markup.escape(&options.module_name(&registrations.modules[module_id].name)),
+                    locale.count(registrants.len(), strings.registrant),
+                    strings.from,
+                    locale.count(num_groups, strings.affiliation),
+                )
+            }),
+    )
+}
+
+/// Write the fraction of accepted people who attended each module
+pub fn write_attendance_report(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    ordered_modules: &[ModuleId],
+    rosters: &[Roster],
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    options.write_heading(out, 1, options.locale.strings().attendance_title)?;
+    markup.write_bullet_list(
+        out,
+        ordered_modules.iter().filter_map(|module_id| {
+            let roster = &rosters[*module_id];
+            if roster.accepted.is_empty() {
+                return None;
+            }
+            let num_present = roster
+                .accepted
+                .iter()
+                .filter(|&&person_id| {
+                    options.is_present(&registrations.persons[person_id].identity) == Some(true)
+                })
+                .count();
+            Some(format!(
+                "{}: {num_present}/{} present ({:.0}%)",
+                markup.escape(&options.module_name(&registrations.modules[*module_id].name)),
+                roster.accepted.len(),
+                100.0 * num_present as f64 / roster.accepted.len() as f64
+            ))
+        }),
+    )
+}
+
+/// Write the list of people registered to too many modules on some day
+pub fn write_daily_overloads(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    overloads: &[DailyOverload],
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    options.write_heading(out, 1, options.locale.strings().daily_overload_title)?;
+    markup.write_bullet_list(
+        out,
+        overloads.iter().map(|overload| {
+            let modules = overload
+                .modules
+                .iter()
+                .map(|&module_id| {
+                    markup
+                        .escape(&options.module_name(&registrations.modules[module_id].name))
+                        .into_owned()
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "{}: {} {} {} ({modules})",
+                registrations.persons[overload.person_id]
+                    .identity
+                    .render(markup, options.identity_format),
+                options
+                    .locale
+                    .count(overload.modules.len(), options.locale.strings().module),
+                options.locale.strings().on_day,
+                overload.date,
+            )
+        }),
+    )
+}
+
+/// Write the list of people who registered to a module after it started
+pub fn write_late_registrations(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    late_registrations: &[(PersonId, ModuleId)],
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    options.write_heading(out, 1, options.locale.strings().late_registrations_title)?;
+    markup.write_bullet_list(
+        out,
+        late_registrations.iter().map(|&(person_id, module_id)| {
+            let person = &registrations.persons[person_id];
+            format!(
+                "{}: {} (registered on {})",
+                person.identity.render(markup, options.identity_format),
+                markup.escape(&options.module_name(&registrations.modules[module_id].name)),
+                markup.escape(&options.format_time(person.registration_time)),
+            )
+        }),
+    )
+}
+
+/// Write who registered to each module after some time, e.g. since the
+/// last run
+///
+/// Modules without new registrations are skipped. New registrants are listed
+/// in roster order, waitlisted people included.
+pub fn write_new_registrations(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    ordered_modules: &[ModuleId],
+    rosters: &[Roster],
+    since: OffsetDateTime,
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    options.write_heading(
+        out,
+        1,
+        &format!(
+            "{} {}",
+            options.locale.strings().new_registrations_title,
+            options.format_time(since)
+        ),
+    )?;
+    for &module_id in ordered_modules {
+        let new_registrants = rosters[module_id]
+            .ranked()
+            .filter(|&(person_id, _, _)| registrations.persons[person_id].registration_time > since)
+            .map(|(person_id, _, _)| person_id)
+            .collect::<Vec<_>>();
+        if new_registrants.is_empty() {
+            continue;
+        }
+        options.write_heading(
+            out,
+            2,
+            &options.module_name(&registrations.modules[module_id].name),
+        )?;
+        markup.write_bullet_list(
+            out,
+            new_registrants.into_iter().map(|person_id| {
+                registrations.persons[person_id]
+                    .identity
+                    .render(markup, options.identity_format)
+            }),
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the people who chose none of the displayed modules
+///
+/// `persons` should come from [`Registrations::persons_outside()`].
+pub fn write_hidden_registrants(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    persons: &[PersonId],
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    options.write_heading(out, 1, options.locale.strings().hidden_title)?;
+    markup.write_bullet_list(
+        out,
+        persons.iter().map(|&person_id| {
+            registrations.persons[person_id]
+                .identity
+                .render(markup, options.identity_format)
+        }),
+    )
+}
+
+/// Write the people whose registration time is implausible
+///
+/// `persons` should come from [`Registrations::out_of_range_registrations()`].
+pub fn write_out_of_range_registrations(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    persons: &[PersonId],
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    options.write_heading(out, 1, options.locale.strings().out_of_range_title)?;
+    markup.write_bullet_list(
+        out,
+        persons.iter().map(|&person_id| {
+            let person = &registrations.persons[person_id];
+            format!(
+                "{}: registered on {}",
+                person.identity.render(markup, options.identity_format),
+                markup.escape(&options.format_time(person.registration_time)),
+            )
+        }),
+    )
+}
+
+/// Write the modules whose registration count differs from expectations
+///
+/// Nothing is written if all counts match. Expected modules which nobody
+/// registered to are reported with a count of 0.
+pub fn write_count_mismatches(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    expected_counts: &[ExpectedCountRecord],
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    let counts = registrations.registration_counts();
+    let mismatches = expected_counts
+        .iter()
+        .filter_map(|ExpectedCountRecord { module, expected }| {
+            let actual = registrations
+                .module_id(module)
+                .map_or(0, |module_id| counts[module_id]);
+            (actual != *expected).then(|| {
+                format!(
+                    "{}: expected {expected}, found {actual}",
+                    markup.escape(module.trim())
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    options.write_heading(out, 1, options.locale.strings().count_mismatches_title)?;
+    markup.write_bullet_list(out, mismatches.into_iter())
+}
+
+/// Write suspicious record IDs, which may come from concatenated exports
+///
+/// `anomalies` should come from [`Registrations::id_anomalies()`]. Nothing is
+/// written if there is no anomaly.
+pub fn write_id_anomalies(
+    out: &mut String,
+    options: &DisplayOptions,
+    anomalies: &[IdAnomaly],
+) -> std::fmt::Result {
+    if anomalies.is_empty() {
+        return Ok(());
+    }
+    options.write_heading(out, 1, options.locale.strings().id_anomalies_title)?;
+    options.format.markup().write_bullet_list(
+        out,
+        anomalies.iter().map(|anomaly| match *anomaly {
+            IdAnomaly::Duplicate { id, count } => {
+                format!("ID {id} is shared by {count} records")
+            }
+            IdAnomaly::Gap { after, before } => {
+                format!("No record between IDs {after} and {before}")
+            }
+        }),
+    )
+}
+
+/// Write the affiliations that no simplification rule matched
+pub fn write_unsimplified_affiliations(
+    out: &mut String,
+    options: &DisplayOptions,
+    affiliations: &[(&str, usize)],
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    options.write_heading(out, 1, options.locale.strings().unsimplified_title)?;
+    markup.write_bullet_list(
+        out,
+        affiliations
+            .iter()
+            .map(|(affiliation, count)| format!("{}: {count}", markup.escape(affiliation))),
+    )
+}
+
+/// Write the people who chose the most modules, with their module count
+pub fn write_top_registrants(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    top_registrants: &[PersonId],
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    options.write_heading(out, 1, options.locale.strings().top_registrants_title)?;
+    markup.write_ordered_list(
+        out,
+        top_registrants.iter().map(|&person_id| {
+            let person = &registrations.persons[person_id];
+            format!(
+                "{}: {}",
+                person.identity.render(markup, options.identity_format),
+                options.locale.count(
+                    person.choice_of_modules.len(),
+                    options.locale.strings().module
+                )
+            )
+        }),
+    )
+}
+
+/// Write the changes of registrants between two registration snapshots
+pub fn write_diff(
+    out: &mut String,
+    options: &DisplayOptions,
+    diffs: &[ModuleDiff],
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    let strings = options.locale.strings();
+    options.write_heading(out, 1, strings.diff_title)?;
+    for diff in diffs {
+        options.write_heading(out, 2, &options.module_name(&diff.module))?;
+        markup.write_paragraph(
+            out,
+            &format!(
+                "{}, {}, {} {:+}",
+                options.locale.count(diff.added.len(), strings.added),
+                options.locale.count(diff.removed.len(), strings.removed),
+                strings.net_change,
+                diff.net_change()
+            ),
+        )?;
+        markup.write_bullet_list(
+            out,
+            (diff
+                .added
+                .iter()
+                .map(|email| format!("{}: {}", strings.added_label, markup.escape(email))))
+            .chain(
+                diff.removed
+                    .iter()
+                    .map(|email| format!("{}: {}", strings.removed_label, markup.escape(email))),
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+/// Explain how someone's module choices were admitted, step by step
+///
+/// `module_rosters` should come from [`Registrations::module_rosters()`], and
+/// `rosters` from [`Limits::admit_all()`] applied to them. The registration
+/// rank is the person's position in the module's registration order, which
+/// may differ from the admission order when fair rotation or choice order
+/// are in use.
+pub fn write_explanation(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    limits: &Limits,
+    module_rosters: &[Vec<PersonId>],
+    rosters: &[Roster],
+    person_id: PersonId,
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    let person = &registrations.persons[person_id];
+    options.write_heading(out, 1, options.locale.strings().explanation_title)?;
+    markup.write_paragraph(
+        out,
+        &format!(
+            "{} registered on {} with priority {}, and chose {}.",
+            person.identity.render(markup, options.identity_format),
+            markup.escape(&options.format_time(person.registration_time)),
+            person.priority,
+            options.locale.count(
+                person.choice_of_modules.len(),
+                options.locale.strings().module
+            )
+        ),
+    )?;
+    markup.write_ordered_list(
+        out,
+        person.choice_of_modules.iter().map(|&module_id| {
+            let module_roster = &module_rosters[module_id];
+            let mut item = format!(
+                "{}: registration rank {} of {}",
+                markup.escape(&options.module_name(&registrations.modules[module_id].name)),
+                module_roster
+                    .iter()
+                    .position(|&registrant| registrant == person_id)
+                    .map_or_else(|| "?".to_owned(), |idx| (idx + 1).to_string()),
+                module_roster.len()
+            );
+            match (
+                limits.capacities.get(&module_id),
+                rosters[module_id].num_seats,
+            ) {
+                (Some(capacity), Some(num_seats)) => {
+                    write!(item, ", capacity {capacity} ({num_seats} seats)")
+                }
+                _ => write!(item, ", no capacity limit"),
+            }
+            .expect("Writing to a String can't fail");
+            let roster = &rosters[module_id];
+            let rank = roster
+                .ranked()
+                .find(|&(registrant, _, _)| registrant == person_id);
+            match rank {
+                Some((_, Admission::Accepted, rank)) => write!(item, ", accepted (seat {rank})"),
+                Some((_, Admission::Waitlisted(reason), rank)) => {
+                    let reason = match reason {
+                        WaitlistReason::Capacity => "module full",
+                        WaitlistReason::AffiliationQuota => "affiliation quota reached",
+                        WaitlistReason::RegistrationState => "waitlisted in Indico",
+                    };
+                    write!(item, ", waitlisted at position {rank} ({reason})")
+                }
+                None => write!(item, ", not listed"),
+            }
+            .expect("Writing to a String can't fail");
+            item
+        }),
+    )
+}
+
+/// Write the people who were selected by a query on their module choices
+///
+/// `title` describes the query, and `modules` are the queried modules. If
+/// `show_choices` is set, each person is listed with the queried modules
+/// that they chose.
+pub fn write_module_query(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    title: &str,
+    modules: &[ModuleId],
+    persons: &[PersonId],
+    show_choices: bool,
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    options.write_heading(out, 1, title)?;
+    markup.write_bullet_list(
+        out,
+        modules.iter().map(|&module_id| {
+            markup
+                .escape(&options.module_name(&registrations.modules[module_id].name))
+                .into_owned()
+        }),
+    )?;
+    markup.write_paragraph(
+        out,
+        &format!(
+            "{}:",
+            options
+                .locale
+                .count(persons.len(), options.locale.strings().person)
+        ),
+    )?;
+    markup.write_ordered_list(
+        out,
+        persons.iter().map(|&person_id| {
+            let person = &registrations.persons[person_id];
+            let mut item = person.identity.render(markup, options.identity_format);
+            if show_choices {
+                let choices = modules
+                    .iter()
+                    .filter(|module_id| person.choice_of_modules.contains(module_id))
+                    .map(|&module_id| {
+                        markup
+                            .escape(&options.module_name(&registrations.modules[module_id].name))
+                            .into_owned()
+                    })
+                    .collect::<Vec<_>>();
+                write!(item, ": {}", choices.join("; ")).expect("Writing to a String can't fail");
+            }
+            item
+        }),
+    )
+}
+
+/// Write the registrants of each affiliation, along with their chosen modules
+///
+/// `groups` should come from [`Registrations::persons_by_affiliation()`].
+/// Chosen modules are ordered by start time, and affiliations are not repeated
+/// for each person.
+pub fn write_affiliation_rosters(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    groups: &[(&str, Vec<PersonId>)],
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    let identity_format = match options.identity_format {
+        IdentityFormat::NameEmailAffiliation => IdentityFormat::NameEmail,
+        IdentityFormat::NameAffiliation => IdentityFormat::NameOnly,
+        other => other,
+    };
+    options.write_heading(out, 1, options.locale.strings().by_affiliation_title)?;
+    for (affiliation, persons) in groups {
+        if affiliation.is_empty() {
+            options.write_heading(out, 2, options.locale.strings().unknown_affiliation)?;
+        } else {
+            options.write_heading(out, 2, affiliation)?;
+        }
+        markup.write_ordered_list(
+            out,
+            persons.iter().map(|&person_id| {
+                let person = &registrations.persons[person_id];
+                let mut schedule = person.choice_of_modules.clone();
+                schedule
+                    .sort_by_key(|&module_id| (registrations.modules[module_id].slot, module_id));
+                let modules = schedule
+                    .into_iter()
+                    .map(|module_id| {
+                        markup
+                            .escape(&options.module_name(&registrations.modules[module_id].name))
+                            .into_owned()
+                    })
+                    .collect::<Vec<_>>();
+                format!(
+                    "{}: {}",
+                    person.identity.render(markup, identity_format),
+                    modules.join("; ")
+                )
+            }),
+        )?;
+    }
+    Ok(())
+}
+
+/// Write groups of people who may be the same person
+pub fn write_possible_duplicates(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    groups: &[Vec<PersonId>],
+) -> std::fmt::Result {
+    let markup = options.format.markup();
+    options.write_heading(out, 1, options.locale.strings().possible_duplicates_title)?;
+    markup.write_bullet_list(
+        out,
+        groups.iter().map(|group| {
+            group
+                .iter()
+                .map(|&person_id| {
+                    registrations.persons[person_id]
+                        .identity
+                        .render(markup, options.identity_format)
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        }),
+    )
+}
+
+/// Write an ordered list of people, with optional waitlisting reasons
+pub fn write_persons(
+    out: &mut String,
+    options: &DisplayOptions,
+    registrations: &Registrations,
+    persons: impl Iterator<Item = (PersonId, Option<WaitlistReason>)>,
+) -> std::fmt::Result {
+    let persons = persons.map(|(person_id, reason)| {
+        let identity = &registrations.persons[person_id].identity;
+        let present = options.is_present(identity);
+        let cohort = options.cohorts.as_ref().map(|cohorts| cohorts[person_id]);
+        (identity, reason, present, cohort)
+    });
+    let vip_marker = |identity: &Identity| {
+        if options.is_vip(identity) {
+            " (VIP)"
+        } else {
+            ""
+        }
+    };
+    if matches!(
+        options.format,
+        OutputFormat::MarkdownTable | OutputFormat::AttendanceSheet
+    ) {
+        let signature = options.format == OutputFormat::AttendanceSheet;
+        let strings = options.locale.strings();
+        write!(
+            out,
+            "| {} | {} | {} |",
+            strings.rank_header, strings.name_header, strings.affiliation_header
+        )?;
+        if options.cohorts.is_some() {
+            write!(out, " {} |", strings.cohort_header)?;
+        }
+        if options.attendance.is_some() {
+            write!(out, " {} |", strings.present_header)?;
+        }
+        if signature {
+            write!(out, " {} |", strings.signature_header)?;
+        }
+        write!(out, "\n|-----:|------|-------------|")?;
+        if options.cohorts.is_some() {
+            write!(out, "--------|")?;
+        }
+        if options.attendance.is_some() {
+            write!(out, "---------|")?;
+        }
+        if signature {
+            write!(out, "------------------------|")?;
+        }
+        writeln!(out)?;
+        for (idx, (identity, reason, present, cohort)) in persons.enumerate() {
+            write!(
+                out,
+                "| {} | {}{} | {}",
+                idx + 1,
+                escape_table_cell(&identity.name),
+                vip_marker(identity),
+                if options.identity_format.shows_affiliation() {
+                    escape_table_cell(identity.simplified_affiliation())
+                } else {
+                    String::new()
+                }
+            )?;
+            match reason {
+                Some(WaitlistReason::AffiliationQuota) => write!(out, " (quota reached) |")?,
+                Some(WaitlistReason::Capacity | WaitlistReason::RegistrationState) | None => {
+                    write!(out, " |")?
+                }
+            }
+            if let Some(cohort) = cohort {
+                write!(out, " {} |", cohort.label())?;
+            }
+            match present {
+                Some(true) => write!(out, " yes |")?,
+                Some(false) => write!(out, " no |")?,
+                None => {}
+            }
+            if signature {
+                write!(out, "{} |", " ".repeat(24))?;
+            }
+            writeln!(out)?;
+        }
+        return Ok(());
+    }
+    let markup = options.format.markup();
+    let items = persons.map(|(identity, reason, present, cohort)| {
+        let mut item = identity.render(markup, options.identity_format);
+        item.push_str(vip_marker(identity));
+        if reason == Some(WaitlistReason::AffiliationQuota) {
+            item.push_str(" (affiliation quota reached)");
+        }
+        if let Some(cohort) = cohort {
+            write!(item, " ({} registrant)", cohort.label())
+                .expect("Writing to a String can't fail");
+        }
+        match present {
+            Some(true) => item.push_str(" (present)"),
+            Some(false) => item.push_str(" (absent)"),
+            None => {}
+        }
+        item
+    });
+    if options.format == OutputFormat::GfmTasklist {
+        for item in items {
+            writeln!(out, "- [ ] {item}")?;
+        }
+        return Ok(());
+    }
+    markup.write_ordered_list(out, items)
+}
+
+/// Write a CSV file with one row per (person, module) registration
+///
+/// This is meant for mail-merge tools. Rows are ordered like the rosters,
+/// and the rank is a person's position in the accepted or waiting list.
+pub fn write_mailmerge_csv(
+    path: &str,
+    registrations: &Registrations,
+    ordered_modules: &[ModuleId],
+    rosters: &[Roster],
+) -> csv::Result<()> {
+    #[derive(Serialize)]
+    struct MailMergeRecord<'a> {
+        email: &'a str,
+        name: &'a str,
+        module: &'a str,
+        rank: usize,
+        status: &'static str,
+    }
+    let mut writer = csv::Writer::from_path(path)?;
+    for &module_id in ordered_modules {
+        for (person_id, status, rank) in rosters[module_id].ranked() {
+            let identity = &registrations.persons[person_id].identity;
+            writer.serialize(MailMergeRecord {
+                email: &identity.email_address,
+                name: &identity.name,
+                module: &registrations.modules[module_id].name,
+                rank,
+                status: status.label(),
+            })?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write a JSON document with the roster of every module
+///
+/// Modules are ordered like the rosters, and each of them has a `count` of
+/// registrants (accepted or waitlisted) on top of the `registrants` array. The
+/// document also has `total_persons` and `total_modules` counts.
+pub fn write_rosters_json(
+    path: &str,
+    registrations: &Registrations,
+    ordered_modules: &[ModuleId],
+    rosters: &[Roster],
+) -> std::io::Result<()> {
+    #[derive(Serialize)]
+    struct RostersReport<'a> {
+        total_persons: usize,
+        total_modules: usize,
+        modules: Vec<ModuleReport<'a>>,
+    }
+    #[derive(Serialize)]
+    struct ModuleReport<'a> {
+        name: &'a str,
+        indico_name: &'a str,
+        count: usize,
+        registrants: Vec<RegistrantReport<'a>>,
+    }
+    #[derive(Serialize)]
+    struct RegistrantReport<'a> {
+        name: &'a str,
+        email: &'a str,
+        affiliation: &'a str,
+        rank: usize,
+        status: &'static str,
+    }
+    let modules = ordered_modules
+        .iter()
+        .map(|&module_id| {
+            let module = &registrations.modules[module_id];
+            let registrants = rosters[module_id]
+                .ranked()
+                .map(|(person_id, status, rank)| {
+                    let identity = &registrations.persons[person_id].identity;
+                    RegistrantReport {
+                        name: &identity.name,
+                        email: &identity.email_address,
+                        affiliation: identity.simplified_affiliation(),
+                        rank,
+                        status: status.label(),
+                    }
+                })
+                .collect::<Vec<_>>();
+            ModuleReport {
+                name: &module.name,
+                indico_name: &module.indico_name,
+                count: registrants.len(),
+                registrants,
+            }
+        })
+        .collect();
+    let json = serde_json::to_string_pretty(&RostersReport {
+        total_persons: registrations.persons.len(),
+        total_modules: registrations.modules.len(),
+        modules,
+    })?;
+    std::fs::write(path, json + "\n")
+}
+
+/// Write a JSON summary of a run, for dashboards
+///
+/// This has overall counts, the size of each module in display order, the
+/// number of people from each simplified affiliation, and the number of data
+/// quality warnings from each category.
+pub fn write_run_report(
+    path: &str,
+    registrations: &Registrations,
+    ordered_modules: &[ModuleId],
+    rosters: &[Roster],
+) -> std::io::Result<()> {
+    #[derive(Serialize)]
+    struct RunReport<'a> {
+        counts: CountsReport,
+        modules: Vec<ModuleReport<'a>>,
+        affiliations: Vec<AffiliationReport<'a>>,
+        warnings: BTreeMap<String, usize>,
+    }
+    #[derive(Serialize)]
+    struct CountsReport {
+        records: usize,
+        persons: usize,
+        merged_duplicates: usize,
+        modules: usize,
+        module_registrations: usize,
+    }
+    #[derive(Serialize)]
+    struct ModuleReport<'a> {
+        name: &'a str,
+        indico_name: &'a str,
+        registered: usize,
+        accepted: usize,
+        waitlisted: usize,
+        capacity: Option<usize>,
+    }
+    #[derive(Serialize)]
+    struct AffiliationReport<'a> {
+        affiliation: Option<&'a str>,
+        count: usize,
+    }
+    let modules = ordered_modules
+        .iter()
+        .map(|&module_id| {
+            let module = &registrations.modules[module_id];
+            let roster = &rosters[module_id];
+            let registered = roster.accepted.len() + roster.waitlisted.len();
+            ModuleReport {
+                name: &module.name,
+                indico_name: &module.indico_name,
+                registered,
+                accepted: roster.accepted.len(),
+                waitlisted: roster.waitlisted.len(),
+                capacity: roster.num_seats,
+            }
+        })
+        .collect();
+    let mut affiliations = HashMap::<&str, usize>::new();
+    for person in &registrations.persons {
+        *affiliations
+            .entry(person.identity.simplified_affiliation().trim())
+            .or_default() += 1;
+    }
+    let mut affiliations = affiliations.into_iter().collect::<Vec<_>>();
+    affiliations
+        .sort_by_key(|&(affiliation, count)| (Reverse(count), affiliation.is_empty(), affiliation));
+    let mut warnings = BTreeMap::new();
+    for warning in &registrations.warnings {
+        *warnings.entry(warning.category()).or_default() += 1;
+    }
+    let json = serde_json::to_string_pretty(&RunReport {
+        counts: CountsReport {
+            records: registrations.num_records,
+            persons: registrations.persons.len(),
+            merged_duplicates: registrations.num_merged_duplicates,
+            modules: registrations.modules.len(),
+            module_registrations: registrations.registration_counts().into_iter().sum(),
+        },
+        modules,
+        affiliations: affiliations
+            .into_iter()
+            .map(|(affiliation, count)| AffiliationReport {
+                affiliation: (!affiliation.is_empty()).then_some(affiliation),
+                count,
+            })
+            .collect(),
+        warnings,
+    })?;
+    std::fs::write(path, json + "\n")
+}
+
+/// Save the latest registration time seen by this run, for use by the next
+/// run, see [`load_last_run()`](crate::input::load_last_run)
+pub fn save_last_run(path: &str, last_registration_time: OffsetDateTime) -> std::io::Result<()> {
+    let state = last_registration_time
+        .format(&Rfc3339)
+        .map_err(std::io::Error::other)?;
+    write_atomically(Path::new(path), &(state + "\n"))
+}
+
+/// Write a vCard file with one contact per distinct email address
+///
+/// Each contact has the person's name, email address and affiliation, as
+/// written in the first registration with this email address.
+pub fn write_vcards(path: &str, registrations: &Registrations) -> std::io::Result<()> {
+    let mut vcf = String::new();
+    let mut emails = HashSet::new();
+    for person in &registrations.persons {
+        let identity = &person.identity;
+        if !emails.insert(normalize_email(&identity.email_address)) {
+            continue;
+        }
+        let mut write_line = |line: String| {
+            // Lines longer than 75 bytes must be folded
+            let mut line_len = 0;
+            for c in line.chars() {
+                if line_len + c.len_utf8() > 75 {
+                    vcf.push_str("\r\n ");
+                    line_len = 1;
+                }
+                vcf.push(c);
+                line_len += c.len_utf8();
+            }
+            vcf.push_str("\r\n");
+        };
+        write_line("BEGIN:VCARD".to_owned());
+        write_line("VERSION:4.0".to_owned());
+        write_line(format!("FN:{}", escape_vcard_text(&identity.name)));
+        write_line(format!(
+            "EMAIL:{}",
+            escape_vcard_text(identity.email_address.trim())
+        ));
+        if !identity.affiliation.trim().is_empty() {
+            write_line(format!(
+                "ORG:{}",
+                escape_vcard_text(identity.affiliation.trim())
+            ));
+        }
+        write_line("END:VCARD".to_owned());
+    }
+    std::fs::write(path, vcf)
+}
+
+/// Escape a text value for use in a vCard property
+fn escape_vcard_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ',' | ';' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render one email per (person, module) registration into a directory
+///
+/// The template may contain the `{{name}}`, `{{email}}`, `{{module}}`,
+/// `{{rank}}` and `{{status}}` placeholders, whose meaning is the same as in
+/// [`write_mailmerge_csv()`]. Each email is written to a file named after
+/// the person's email address and the module. If several registrations would
+/// get the same file name, e.g. because someone registered twice, a `-2`,
+/// `-3`... suffix is added so that no email is overwritten.
+pub fn render_emails(
+    template: &str,
+    dir: &str,
+    registrations: &Registrations,
+    ordered_modules: &[ModuleId],
+    rosters: &[Roster],
+) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let mut file_names = HashSet::new();
+    for &module_id in ordered_modules {
+        let module_name = &registrations.modules[module_id].name;
+        for (person_id, status, rank) in rosters[module_id].ranked() {
+            let identity = &registrations.persons[person_id].identity;
+            let email = render_template(
+                template,
+                &[
+                    ("name", &identity.name),
+                    ("email", &identity.email_address),
+                    ("module", module_name),
+                    ("rank", &rank.to_string()),
+                    ("status", status.label()),
+                ],
+            );
+            let stem = format!(
+                "{}--{}",
+                slugify(&identity.email_address),
+                slugify(module_name)
+            );
+            let mut file_name = format!("{stem}.txt");
+            let mut suffix = 1;
+            while !file_names.insert(file_name.clone()) {
+                suffix += 1;
+                file_name = format!("{stem}-{suffix}.txt");
+            }
+            std::fs::write(Path::new(dir).join(file_name), email)?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace `{{key}}` placeholders in a template with the matching values
+///
+/// Unknown placeholders are left as is. The template is scanned only once,
+/// so placeholders that appear within the values are not replaced.
+pub fn render_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        let after_braces = &rest[start + 2..];
+        let placeholder = after_braces.find("}}").and_then(|end| {
+            let key = &after_braces[..end];
+            let value = values.iter().find(|(known, _)| *known == key)?.1;
+            Some((value, &after_braces[end + 2..]))
+        });
+        match placeholder {
+            Some((value, after_placeholder)) => {
+                result.push_str(value);
+                rest = after_placeholder;
+            }
+            None => {
+                result.push_str("{{");
+                rest = after_braces;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Turn text into a lowercase identifier made of letters, digits and dashes
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_owned()
+}
+
+/// Write an Excel workbook with one worksheet per module
+///
+/// Worksheets are named after the module, in slugified form, and list the
+/// accepted people followed by the waiting list, ranked like in
+/// [`Roster::ranked()`]. Modules are ordered as specified, and empty rosters
+/// are skipped.
+#[cfg(feature = "xlsx")]
+pub fn write_xlsx(
+    path: &str,
+    registrations: &Registrations,
+    ordered_modules: &[ModuleId],
+    rosters: &[Roster],
+) -> Result<(), rust_xlsxwriter::XlsxError> {
+    let mut workbook = rust_xlsxwriter::Workbook::new();
+    let mut sheet_names = HashSet::new();
+    for &module_id in ordered_modules {
+        let roster = &rosters[module_id];
+        if roster.is_empty() {
+            continue;
+        }
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(unique_sheet_name(
+            &registrations.modules[module_id].name,
+            &mut sheet_names,
+        ))?;
+        for (col, header) in ["Rank", "Name", "Email", "Affiliation", "Status"]
+            .into_iter()
+            .enumerate()
+        {
+            sheet.write_string(0, col as u16, header)?;
+        }
+        for (idx, (person_id, status, rank)) in roster.ranked().enumerate() {
+            let row = idx as u32 + 1;
+            let identity = &registrations.persons[person_id].identity;
+            sheet.write_number(row, 0, rank as u32)?;
+            sheet.write_string(row, 1, &*identity.name)?;
+            sheet.write_string(row, 2, &*identity.email_address)?;
+            sheet.write_string(row, 3, identity.simplified_affiliation())?;
+            sheet.write_string(row, 4, status.label())?;
+        }
+    }
+    workbook.save(path)
+}
+
+/// Slugify a module name into a valid worksheet name, which is not in use yet
+///
+/// Worksheet names are limited to 31 characters, so long names are
+/// truncated, and a numerical suffix is added when names collide.
+#[cfg(feature = "xlsx")]
+fn unique_sheet_name(module_name: &str, used_names: &mut HashSet<String>) -> String {
+    const MAX_LEN: usize = 31;
+    let slug = slugify(module_name);
+    let slug = if slug.is_empty() { "module" } else { &slug };
+    let truncate = |s: &str, len: usize| s.chars().take(len).collect::<String>();
+    let mut name = truncate(slug, MAX_LEN);
+    let mut suffix = 1;
+    while !used_names.insert(name.to_lowercase()) {
+        suffix += 1;
+        let suffix = format!("-{suffix}");
+        name = truncate(slug, MAX_LEN - suffix.len()) + &suffix;
+    }
+    name
+}
+
+/// Write a file so that readers never observe it partially written
+///
+/// The contents are first written to a temporary file in the same directory,
+/// which is then renamed over the destination. If anything fails, the
+/// temporary file is removed and the destination is left untouched.
+pub fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    let file_name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Output path {} is not a file path", path.display()),
+        )
+    })?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+    let result = (|| {
+        let mut file = std::fs::File::create(&temp_path)?;
+        std::io::Write::write_all(&mut file, contents.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Escape a string so that it can be used as a Markdown table cell
+pub fn escape_table_cell(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{read_raw_records, ColumnMapping};
+
+    #[test]
+    fn template_values_are_not_rendered() {
+        assert_eq!(
+            render_template(
+                "{{name}} is {{status}} {{oops",
+                &[("name", "Alice {{status}}"), ("status", "accepted")]
+            ),
+            "Alice {{status}} is accepted {{oops"
+        );
+    }
+
+    #[test]
+    fn table_cells_escape_pipes() {
+        let csv = "ID,Name,Email Address,Affiliation,Choice of modules,Registration date,Registration state\n\
+                   1,Alice | Bob,alice@example.org,LAL | IJCLab,\"Rust intro 03/15, 14h00\",2024-02-01 10:00:00.0+01:00,Complete";
+        let records = read_raw_records(
+            csv::Reader::from_reader(csv.as_bytes()),
+            &ColumnMapping::default(),
+        )
+        .unwrap();
+        let registrations = Registrations::try_new(records, Default::default()).unwrap();
+        let limits = Limits::default();
+        let rosters = limits.admit_all(&registrations, registrations.module_rosters(None));
+        let options = DisplayOptions {
+            format: OutputFormat::MarkdownTable,
+            ..Default::default()
+        };
+        let mut out = String::new();
+        write_rosters(&mut out, &options, &registrations, &[0], &rosters).unwrap();
+        assert!(
+            out.contains("| 1 | Alice \\| Bob | LAL \\| IJCLab |\n"),
+            "Unexpected table:\n{out}"
+        );
+    }
+
+    #[cfg(feature = "xlsx")]
+    #[test]
+    fn xlsx_workbook() {
+        use crate::admission::Capacity;
+        use std::io::Read;
+        let csv = "ID,Name,Email Address,Affiliation,Choice of modules,Registration date,Registration state\n\
+                   1,Alice,alice@example.org,LAL,\"Rust intro 15/03, 14h00; Deep Learning 16/03, 09:30\",2024-02-01 10:00:00.0+01:00,Complete\n\
+                   2,Bob,bob@example.org,LAL,\"Rust intro 15/03, 14h00\",2024-02-02 10:00:00.0+01:00,Complete\n\
+                   3,Carol,carol@example.org,LAL,\"Rust intro 15/03, 14h00\",2024-02-03 10:00:00.0+01:00,Complete";
+        let records = read_raw_records(
+            csv::Reader::from_reader(csv.as_bytes()),
+            &ColumnMapping::default(),
+        )
+        .unwrap();
+        let registrations = Registrations::try_new(records, Default::default()).unwrap();
+        let rust = registrations.module_id("Rust intro 15/03, 14h00").unwrap();
+        let deep_learning = registrations
+            .module_id("Deep Learning 16/03, 09:30")
+            .unwrap();
+        let mut limits = Limits::default();
+        limits.capacities.insert(rust, Capacity::Seats(2));
+        let rosters = limits.admit_all(&registrations, registrations.module_rosters(None));
+        let path = std::env::temp_dir().join(format!(
+            "indico-transpose-{}-rosters.xlsx",
+            std::process::id()
+        ));
+        let path_str = path.to_str().unwrap();
+        write_xlsx(path_str, &registrations, &[rust, deep_learning], &rosters).unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let mut read = |name: &str| {
+            let mut content = String::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            content
+        };
+        let workbook = read("xl/workbook.xml");
+        let first_sheet = read("xl/worksheets/sheet1.xml");
+        let second_sheet = read("xl/worksheets/sheet2.xml");
+        std::fs::remove_file(&path).unwrap();
+        assert!(workbook.contains("name=\"rust-intro-15-03-14h00\""));
+        assert!(workbook.contains("name=\"deep-learning-16-03-09-30\""));
+
+        // Header, two accepted people, and one waitlisted person ranked first
+        assert_eq!(first_sheet.matches("<row ").count(), 4);
+        assert!(first_sheet.contains("<c r=\"A4\"><v>1</v></c>"));
+        assert_eq!(second_sheet.matches("<row ").count(), 2);
+    }
+}
@@ -0,0 +1,1657 @@
+//! Pre-digested registration data
+
+use crate::input::{
+    read_raw_records_noting_unmapped, skip_preamble, warn_about_unmapped_columns, CSVRecord,
+    ColumnMapping, Identity,
+};
+use clap::ValueEnum;
+use log::{debug, warn};
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    io::BufRead,
+    rc::Rc,
+    sync::OnceLock,
+};
+use time::{Date, Duration, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+use unicode_normalization::UnicodeNormalization;
+
+/// Exploitable version of the Indico registration records
+#[derive(Debug, Default)]
+pub struct Registrations {
+    /// List of people who registered to courses
+    pub persons: Vec<Person>,
+
+    /// List of pedagogical modules
+    pub modules: Vec<Module>,
+
+    /// Number of registration records in the Indico CSV
+    pub num_records: usize,
+
+    /// Number of registration records that were merged into a previous
+    /// record from the same person (only nonzero when deduplicating)
+    pub num_merged_duplicates: usize,
+
+    /// IDs of the registration records that have one, in input order
+    pub record_ids: Vec<usize>,
+
+    /// Data quality issues that were found in the registration records
+    pub warnings: Vec<DataWarning>,
+}
+
+/// Data quality issue found in the registration records
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "category", rename_all = "snake_case")]
+pub enum DataWarning {
+    /// The start time of a module could not be parsed from its name
+    UnparsedModuleTime { module: Box<str> },
+
+    /// Someone registered without choosing any module
+    EmptyChoiceOfModules { name: Box<str>, email: Box<str> },
+
+    /// Several registration records share an email address
+    DuplicateEmail { email: Box<str>, merged: bool },
+
+    /// Someone registered with an email address that doesn't look valid
+    InvalidEmail { name: Box<str>, email: Box<str> },
+
+    /// Several entries of the module metadata file designate the same module,
+    /// but with different metadata
+    ConflictingModuleMetadata { module: Box<str> },
+
+    /// A module was chosen by few people and has almost the same name as a
+    /// more popular module starting at the same time
+    PossiblyMisspelledModule {
+        module: Box<str>,
+        suggestion: Box<str>,
+    },
+}
+//
+impl DataWarning {
+    /// Category of this warning, as it appears in JSON output
+    /// (e.g. "unparsed_module_time")
+    pub fn category(&self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|value| value["category"].as_str().map(str::to_owned))
+            .expect("Warnings are serialized with their category")
+    }
+}
+//
+impl Display for DataWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnparsedModuleTime { module } => write!(
+                f,
+                "Couldn't parse start time of module \"{module}\", it will be unordered in output"
+            ),
+            Self::EmptyChoiceOfModules { name, email } => {
+                write!(f, "{name} <{email}> did not choose any module")
+            }
+            Self::DuplicateEmail { email, merged } => {
+                write!(f, "Several registrations use email address <{email}>")?;
+                if *merged {
+                    write!(f, ", they were merged")?;
+                }
+                Ok(())
+            }
+            Self::InvalidEmail { name, email } => {
+                write!(f, "{name} has an invalid email address <{email}>")
+            }
+            Self::ConflictingModuleMetadata { module } => write!(
+                f,
+                "Module \"{module}\" has conflicting entries in the metadata file"
+            ),
+            Self::PossiblyMisspelledModule { module, suggestion } => write!(
+                f,
+                "Module \"{module}\" may be a misspelling, did you mean \"{suggestion}\"?"
+            ),
+        }
+    }
+}
+
+/// Year of module schedules which only give a day and month, unless
+/// [`ProcessingOptions::year`] says otherwise
+pub const DEFAULT_YEAR: i32 = 2024;
+
+/// Knobs controlling how raw Indico records are post-processed
+#[derive(Clone, Debug, Default)]
+pub struct ProcessingOptions {
+    /// Remove schedule information from displayed module names
+    ///
+    /// See [`Module::new()`] for details.
+    pub strip_date_from_name: bool,
+
+    /// Where the schedule information is located in module names
+    ///
+    /// See [`Module::new()`] for details.
+    pub name_layout: Option<NameLayout>,
+
+    /// Merge registration records that share an email address
+    ///
+    /// The merged person is registered to the union of the modules that were
+    /// chosen across all records, at the earliest registration time.
+    pub dedup: bool,
+
+    /// Keep module IDs in the order where modules are first seen in the CSV
+    ///
+    /// By default, modules are renumbered by name, see
+    /// [`Registrations::sort_modules_by_name()`].
+    pub preserve_input_order: bool,
+
+    /// Granularity of module time slots, in minutes
+    ///
+    /// When ordering modules, start times are rounded to the nearest multiple
+    /// of this granularity, so that modules which start a minute apart are
+    /// considered to start in the same time slot. Displayed start times are
+    /// not rounded.
+    pub module_time_granularity: Option<u32>,
+
+    /// Also recognize module schedules with English or French month names
+    ///
+    /// Schedules like "15 March, 14h00" or "15 mars, 14h00" are then parsed
+    /// when the usual "03/15, 14h00" form is not found.
+    pub month_names: bool,
+
+    /// Names of modules whose start time should not be parsed
+    ///
+    /// Some module titles contain something that looks like a schedule, e.g.
+    /// "Rust 2024/25 news". These modules are treated as unscheduled, and
+    /// their name is left as is. Names are compared as in the Indico CSV,
+    /// ignoring whitespace differences.
+    pub unparsed_modules: Vec<String>,
+
+    /// Merge modules whose names are at least this similar
+    ///
+    /// Similarity is the normalized Damerau-Levenshtein similarity of the
+    /// lowercase Indico names, from 0 to 1, and only modules starting in the
+    /// same time slot are merged. Each module chosen by fewer people is
+    /// merged into the most popular similar module, whose name is kept. This
+    /// can merge modules which are genuinely different, so it is disabled by
+    /// default and every merge is logged.
+    pub fuzzy_modules: Option<f64>,
+
+    /// Year of module schedules, which only give a day and month
+    ///
+    /// The default is [`DEFAULT_YEAR`]. ISO-8601 schedules, which have a year,
+    /// are not affected.
+    pub year: Option<i32>,
+
+    /// Only keep module choices whose name matches this regular expression
+    ///
+    /// Names are matched as in the Indico CSV. Other module choices are
+    /// dropped while loading, as if nobody had chosen these modules.
+    pub module_filter: Option<Regex>,
+
+    /// Only keep registration records whose affiliation matches this regular
+    /// expression
+    ///
+    /// Affiliations are matched as in the Indico CSV, before simplification.
+    /// Other records are dropped while loading, and not counted as records.
+    pub affiliation_filter: Option<Regex>,
+}
+//
+impl ProcessingOptions {
+    /// Truth that a module's start time should not be parsed, see
+    /// [`ProcessingOptions::unparsed_modules`]
+    pub fn is_unparsed(&self, module_name: &str) -> bool {
+        let module_name = collapse_whitespace(module_name);
+        self.unparsed_modules
+            .iter()
+            .any(|unparsed| collapse_whitespace(unparsed) == module_name)
+    }
+}
+
+/// Configurable way to load [`Registrations`] from Indico CSV exports
+///
+/// This gives library users access to the same knobs as the command line.
+/// Settings which are not specified keep their default value.
+#[derive(Clone, Debug, Default)]
+pub struct RegistrationsBuilder {
+    /// How raw records are post-processed
+    options: ProcessingOptions,
+
+    /// Extra CSV columns that should be read
+    columns: ColumnMapping,
+
+    /// Number of preamble lines before the CSV header of each input
+    skip_lines: usize,
+
+    /// Maximal number of records that should be processed, if any
+    sample: Option<usize>,
+}
+//
+impl RegistrationsBuilder {
+    /// Start with the default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set all post-processing options at once
+    pub fn options(mut self, options: ProcessingOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Merge registration records that share an email address
+    ///
+    /// See [`ProcessingOptions::dedup`] for details.
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.options.dedup = dedup;
+        self
+    }
+
+    /// Remove schedule information from displayed module names
+    ///
+    /// See [`ProcessingOptions::strip_date_from_name`] for details.
+    pub fn strip_date_from_name(mut self, strip: bool) -> Self {
+        self.options.strip_date_from_name = strip;
+        self
+    }
+
+    /// Also recognize module schedules with English or French month names
+    ///
+    /// See [`ProcessingOptions::month_names`] for details.
+    pub fn month_names(mut self, month_names: bool) -> Self {
+        self.options.month_names = month_names;
+        self
+    }
+
+    /// Merge modules with similar names
+    ///
+    /// See [`ProcessingOptions::fuzzy_modules`] for details.
+    pub fn fuzzy_modules(mut self, min_similarity: Option<f64>) -> Self {
+        self.options.fuzzy_modules = min_similarity;
+        self
+    }
+
+    /// Year of module schedules which only give a day and month
+    ///
+    /// See [`ProcessingOptions::year`] for details.
+    pub fn year(mut self, year: i32) -> Self {
+        self.options.year = Some(year);
+        self
+    }
+
+    /// Only keep module choices whose name matches a regular expression
+    ///
+    /// See [`ProcessingOptions::module_filter`] for details.
+    pub fn module_filter(mut self, filter: Option<Regex>) -> Self {
+        self.options.module_filter = filter;
+        self
+    }
+
+    /// Only keep registration records whose affiliation matches a regular
+    /// expression
+    ///
+    /// See [`ProcessingOptions::affiliation_filter`] for details.
+    pub fn affiliation_filter(mut self, filter: Option<Regex>) -> Self {
+        self.options.affiliation_filter = filter;
+        self
+    }
+
+    /// Time zone offset of registration times which don't specify one
+    ///
+    /// See [`ColumnMapping::assumed_offset`] for details.
+    pub fn assume_offset(mut self, offset: UtcOffset) -> Self {
+        self.columns.assumed_offset = Some(offset);
+        self
+    }
+
+    /// Accept registration times which are only a date
+    ///
+    /// See [`ColumnMapping::allow_date_only`] for details.
+    pub fn allow_date_only(mut self, allow: bool) -> Self {
+        self.columns.allow_date_only = allow;
+        self
+    }
+
+    /// Read extra CSV columns, like a priority column
+    ///
+    /// This replaces the time parsing settings of
+    /// [`RegistrationsBuilder::assume_offset()`] and
+    /// [`RegistrationsBuilder::allow_date_only()`], which belong to the
+    /// column mapping.
+    pub fn columns(mut self, columns: ColumnMapping) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Skip this many preamble lines before the CSV header of each input
+    pub fn skip_lines(mut self, skip_lines: usize) -> Self {
+        self.skip_lines = skip_lines;
+        self
+    }
+
+    /// Only process the first `sample` records, across all inputs
+    pub fn sample(mut self, sample: Option<usize>) -> Self {
+        self.sample = sample;
+        self
+    }
+
+    /// Post-process registration records that were already read out
+    ///
+    /// The column mapping and preamble settings do not apply here, as they
+    /// only affect how records are read out.
+    pub fn build(
+        self,
+        raw_records: impl IntoIterator<Item = csv::Result<CSVRecord>>,
+    ) -> csv::Result<Registrations> {
+        Registrations::try_new(
+            raw_records
+                .into_iter()
+                .take(self.sample.unwrap_or(usize::MAX)),
+            self.options,
+        )
+    }
+
+    /// Read out and post-process the registration records of several Indico
+    /// CSV exports, in order
+    ///
+    /// CSV columns that this program doesn't know about are reported once,
+    /// however many inputs have them.
+    pub fn read(
+        self,
+        inputs: impl IntoIterator<Item = impl BufRead>,
+    ) -> csv::Result<Registrations> {
+        let mut raw_records = Vec::new();
+        let mut unmapped_columns = Vec::new();
+        for mut input in inputs {
+            skip_preamble(&mut input, self.skip_lines)?;
+            raw_records.push(read_raw_records_noting_unmapped(
+                csv::Reader::from_reader(input),
+                &self.columns,
+                &mut unmapped_columns,
+            )?);
+        }
+        warn_about_unmapped_columns(&unmapped_columns);
+        self.build(raw_records.into_iter().flatten())
+    }
+}
+
+/// Index of a person within Registrations::persons
+pub type PersonId = usize;
+
+/// Index of a module within Registrations::modules
+pub type ModuleId = usize;
+
+/// What we need to know about someone who registered to modules
+#[derive(Debug)]
+pub struct Person {
+    /// Information identifying this person
+    pub identity: Identity,
+
+    /// Which modules they chose to attend
+    pub choice_of_modules: Vec<ModuleId>,
+
+    /// Time at which they registered
+    pub registration_time: OffsetDateTime,
+
+    /// Registration priority (higher goes first, default is 0)
+    pub priority: i64,
+
+    /// Registration state from Indico, if known
+    pub registration_state: Option<Box<str>>,
+}
+//
+impl Person {
+    /// Key by which people are ordered in module rosters
+    ///
+    /// People are ordered by decreasing priority, then by registration time,
+    /// optionally rounded down to a coarser time unit. Ties are broken by
+    /// email address, then by input order.
+    pub fn registration_order(
+        &self,
+        person_id: PersonId,
+        rounding: Option<TimeUnit>,
+    ) -> (Reverse<i64>, OffsetDateTime, Box<str>, PersonId) {
+        let registration_time = match rounding {
+            Some(unit) => unit.round_down(self.registration_time),
+            None => self.registration_time,
+        };
+        (
+            Reverse(self.priority),
+            registration_time,
+            normalize_email(&self.identity.email_address),
+            person_id,
+        )
+    }
+}
+
+/// Location of the schedule information in module names
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum NameLayout {
+    /// Module title followed by schedule, e.g. "Rust intro - 15/03, 14h00"
+    DateSuffix,
+
+    /// Schedule followed by module title, e.g. "15/03, 14h00: Rust intro"
+    DatePrefix,
+}
+
+/// Where modules whose start time is unknown go, when ordering modules by
+/// a canonical module list
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum UnscheduledPlacement {
+    /// Right after the scheduled module that precedes them in the list
+    #[default]
+    Interleave,
+
+    /// After all scheduled modules, in list order
+    Last,
+}
+
+/// Time unit to which registration times can be rounded
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TimeUnit {
+    Minute,
+    Hour,
+    Day,
+}
+//
+impl TimeUnit {
+    /// Round a date/time down to the beginning of the enclosing time unit
+    pub fn round_down(self, time: OffsetDateTime) -> OffsetDateTime {
+        let time = time
+            .replace_nanosecond(0)
+            .expect("0 is a valid nanosecond")
+            .replace_second(0)
+            .expect("0 is a valid second");
+        match self {
+            Self::Minute => time,
+            Self::Hour => time.replace_minute(0).expect("0 is a valid minute"),
+            Self::Day => time.replace_time(Time::MIDNIGHT),
+        }
+    }
+}
+
+/// Range of plausible registration dates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegistrationWindow {
+    /// First plausible registration date
+    pub start: Date,
+
+    /// Last plausible registration date
+    pub end: Date,
+}
+//
+impl RegistrationWindow {
+    /// Parse a window of the form "YYYY-MM-DD..YYYY-MM-DD", bounds included
+    pub fn parse(window: &str) -> Result<Self, String> {
+        let (start, end) = window
+            .split_once("..")
+            .ok_or_else(|| format!("expected START..END, found \"{window}\""))?;
+        let parse_date = |date: &str| {
+            Date::parse(
+                date.trim(),
+                time::macros::format_description!("[year]-[month]-[day]"),
+            )
+            .map_err(|e| format!("invalid date \"{}\" ({e})", date.trim()))
+        };
+        let (start, end) = (parse_date(start)?, parse_date(end)?);
+        if start > end {
+            return Err(format!("window starts on {start}, after its end on {end}"));
+        }
+        Ok(Self { start, end })
+    }
+
+    /// Truth that a registration time is within this window
+    ///
+    /// The registration date is taken in the time zone used by Indico.
+    pub fn contains(&self, time: OffsetDateTime) -> bool {
+        (self.start..=self.end).contains(&time.date())
+    }
+}
+
+/// What we know about a module
+#[derive(Debug)]
+pub struct Module {
+    /// Name of the module in the Indico CSV
+    pub indico_name: Rc<str>,
+
+    /// Name of the module, as displayed in the output
+    pub name: Rc<str>,
+
+    /// Date and time at which the module will start
+    pub start_time: OffsetDateTime,
+
+    /// Start time rounded to the time slot granularity, which is used when
+    /// ordering modules by start time
+    ///
+    /// See [`ProcessingOptions::module_time_granularity`] for details.
+    pub slot: OffsetDateTime,
+}
+//
+impl Module {
+    /// Create a new module entry from the module name in Indico CSV
+    ///
+    /// If `options.strip_date_from_name` is set, the schedule information
+    /// from which the start time is parsed is removed from the displayed
+    /// module name, as directed by `options.name_layout`.
+    pub fn new(module_name: &str, options: &ProcessingOptions) -> Self {
+        debug!("- Registered new module: {module_name}");
+        let mut name = Rc::<str>::from(module_name);
+        let year = options.year.unwrap_or(DEFAULT_YEAR);
+        let schedule = if options.is_unparsed(module_name) {
+            debug!("  * Not parsing its start time, as requested");
+            None
+        } else if let Some(schedule) = Self::parse_iso_schedule(module_name) {
+            Some(schedule)
+        } else if let Some(schedule) = Self::parse_day_month_schedule(module_name, year) {
+            Some(schedule)
+        } else if options.month_names {
+            Self::parse_month_name_schedule(module_name, year)
+        } else {
+            None
+        };
+        let start_time = if let Some((schedule, date, time)) = schedule {
+            if options.strip_date_from_name {
+                let (before, after) = (
+                    &module_name[..schedule.start()],
+                    &module_name[schedule.end()..],
+                );
+                let is_separator = |c: char| c.is_whitespace() || "-–—,:;/([".contains(c);
+                let stripped = match options.name_layout {
+                    None => format!("{before}{after}").trim().to_owned(),
+                    Some(NameLayout::DateSuffix) => {
+                        before.trim_end_matches(is_separator).to_owned()
+                    }
+                    Some(NameLayout::DatePrefix) => after
+                        .trim_start_matches(|c: char| is_separator(c) || ")]".contains(c))
+                        .to_owned(),
+                };
+                let stripped = stripped.trim();
+                if stripped.is_empty() {
+                    warn!(
+                        "Not stripping date from module \"{module_name}\" as nothing would remain"
+                    );
+                } else {
+                    debug!("  * Displayed as: {stripped}");
+                    name = stripped.into();
+                }
+            }
+            OffsetDateTime::new_utc(date, time)
+        } else {
+            OffsetDateTime::new_utc(Date::MAX, Time::MIDNIGHT)
+        };
+        let slot = match options.module_time_granularity {
+            Some(granularity) if granularity > 0 && start_time.date() != Date::MAX => {
+                let granularity = i64::from(granularity);
+                let minutes = i64::from(start_time.hour()) * 60 + i64::from(start_time.minute());
+                let rounded = (minutes + granularity / 2) / granularity * granularity;
+                start_time.replace_time(Time::MIDNIGHT) + Duration::minutes(rounded)
+            }
+            _ => start_time,
+        };
+        Self {
+            indico_name: module_name.into(),
+            name,
+            start_time,
+            slot,
+        }
+    }
+
+    /// Find a module schedule written as an ISO-8601 date and time, like
+    /// "2024-03-15T14:00"
+    ///
+    /// Seconds and a UTC offset may follow, but the time is used as written,
+    /// like other schedules which are all taken in the event's time zone.
+    /// Returns the matched schedule, the date and the time.
+    fn parse_iso_schedule(module_name: &str) -> Option<(regex::Match<'_>, Date, Time)> {
+        static ISO_REGEX: OnceLock<Regex> = OnceLock::new();
+        let iso_regex = ISO_REGEX.get_or_init(|| {
+            Regex::new(
+                r"\b([0-9]{4})-([0-9]{2})-([0-9]{2})[T ]([0-9]{2}):([0-9]{2})(?::[0-9]{2})?(?:Z|[+-][0-9]{2}:?[0-9]{2})?\b",
+            )
+            .expect("Regex was manually checked")
+        });
+        let captures = iso_regex.captures(module_name)?;
+        let (_, [year, month, day, hour, min]) = captures.extract();
+        let year = year.parse::<i32>().unwrap();
+        let [month, day, hour, min] = [month, day, hour, min].map(|s| s.parse::<u8>().unwrap());
+        let date = Date::from_calendar_date(year, Month::try_from(month).ok()?, day).ok()?;
+        let time = Time::from_hms(hour, min, 0).ok()?;
+        Some((
+            captures.get(0).expect("Group 0 is always present"),
+            date,
+            time,
+        ))
+    }
+
+    /// Find a module schedule written as a day and month, like "15/03, 14h00",
+    /// taking place in some year
+    ///
+    /// Month numbers past 12 wrap around, as in [`Month::nth_next()`]. Returns
+    /// the matched schedule, the date and the time, or `None` if the date or
+    /// time is invalid.
+    fn parse_day_month_schedule(
+        module_name: &str,
+        year: i32,
+    ) -> Option<(regex::Match<'_>, Date, Time)> {
+        static DAY_MONTH_REGEX: OnceLock<Regex> = OnceLock::new();
+        let day_month_regex = DAY_MONTH_REGEX.get_or_init(|| {
+            Regex::new(
+                r"([0-9]{1,2})/([0-9]{1,2})(?: \+ [a-z]+. [0-9]+/[0-9]+)?, ([0-9]{1,2})[:h]([0-9]{1,2})",
+            )
+            .expect("Regex was manually checked")
+        });
+        let captures = day_month_regex.captures(module_name)?;
+        let (_, [day, month, hour, min]) = captures.extract();
+        let [day, month, hour, min] = [day, month, hour, min].map(|s| s.parse::<u8>().unwrap());
+        let month = Month::January.nth_next(month.checked_sub(1)?);
+        let date = Date::from_calendar_date(year, month, day).ok()?;
+        let time = Time::from_hms(hour, min, 0).ok()?;
+        Some((
+            captures.get(0).expect("Group 0 is always present"),
+            date,
+            time,
+        ))
+    }
+
+    /// Find a module schedule written with a month name, like "15 March, 14h00",
+    /// taking place in some year
+    ///
+    /// Returns the matched schedule, the date and the time, or `None` if the
+    /// date or time is invalid.
+    fn parse_month_name_schedule(
+        module_name: &str,
+        year: i32,
+    ) -> Option<(regex::Match<'_>, Date, Time)> {
+        static MONTH_NAME_REGEX: OnceLock<Regex> = OnceLock::new();
+        let month_name_regex = MONTH_NAME_REGEX.get_or_init(|| {
+            Regex::new(
+                r"(?i)\b([0-9]{1,2})(?:er)? (january|february|march|april|may|june|july|august|september|october|november|december|janvier|février|fevrier|mars|avril|mai|juin|juillet|août|aout|septembre|octobre|novembre|décembre|decembre),? ([0-9]{1,2})[:h]([0-9]{1,2})",
+            )
+            .expect("Regex was manually checked")
+        });
+        let captures = month_name_regex.captures(module_name)?;
+        let (_, [day, month, hour, min]) = captures.extract();
+        let month = match month.to_lowercase().as_str() {
+            "january" | "janvier" => Month::January,
+            "february" | "février" | "fevrier" => Month::February,
+            "march" | "mars" => Month::March,
+            "april" | "avril" => Month::April,
+            "may" | "mai" => Month::May,
+            "june" | "juin" => Month::June,
+            "july" | "juillet" => Month::July,
+            "august" | "août" | "aout" => Month::August,
+            "september" | "septembre" => Month::September,
+            "october" | "octobre" => Month::October,
+            "november" | "novembre" => Month::November,
+            "december" | "décembre" | "decembre" => Month::December,
+            _ => unreachable!("Regex only matches known month names"),
+        };
+        let [day, hour, min] = [day, hour, min].map(|s| s.parse::<u8>().unwrap());
+        let date = Date::from_calendar_date(year, month, day).ok()?;
+        let time = Time::from_hms(hour, min, 0).ok()?;
+        Some((
+            captures.get(0).expect("Group 0 is always present"),
+            date,
+            time,
+        ))
+    }
+
+    /// Truth that the start time of this module could be parsed
+    ///
+    /// Modules whose start time is unknown are given a start time at the end
+    /// of time, so that they come last in the output.
+    pub fn is_scheduled(&self) -> bool {
+        self.start_time.date() != Date::MAX
+    }
+}
+
+/// Someone who registered to too many modules on a single day
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DailyOverload {
+    /// Overloaded person
+    pub person_id: PersonId,
+
+    /// Day on which they are overloaded
+    pub date: Date,
+
+    /// Modules they registered to on this day, ordered by start time
+    pub modules: Vec<ModuleId>,
+}
+
+/// Third of the registrants that someone belongs to, by registration time
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Cohort {
+    /// First third of registrants
+    Early,
+
+    /// Second third of registrants
+    Mid,
+
+    /// Last third of registrants
+    Late,
+}
+//
+impl Cohort {
+    /// Short label of this cohort
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Early => "early",
+            Self::Mid => "mid",
+            Self::Late => "late",
+        }
+    }
+}
+
+/// Change of a module's registrants between two registration snapshots
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleDiff {
+    /// Displayed name of the module
+    pub module: Rc<str>,
+
+    /// Emails of people who registered since the old snapshot, sorted
+    pub added: Vec<Box<str>>,
+
+    /// Emails of people who are no longer registered, sorted
+    pub removed: Vec<Box<str>>,
+}
+//
+impl ModuleDiff {
+    /// Change in the number of registrants
+    pub fn net_change(&self) -> isize {
+        self.added.len() as isize - self.removed.len() as isize
+    }
+}
+
+/// Suspicious pattern in registration record IDs, which may indicate that
+/// several exports were concatenated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdAnomaly {
+    /// Several records share the same ID
+    Duplicate { id: usize, count: usize },
+
+    /// No record has an ID between these two IDs
+    Gap { after: usize, before: usize },
+}
+
+impl Registrations {
+    /// Find a module by its name, warning if it doesn't exist
+    ///
+    /// Both the name from the Indico CSV and the displayed name are accepted.
+    ///
+    /// `what` describes what the module name is being looked up for, which
+    /// is used to make the warning more informative.
+    pub fn find_module(&self, module_name: &str, what: &str) -> Option<ModuleId> {
+        let module_name = module_name.trim();
+        let module_id = self.module_id(module_name);
+        if module_id.is_none() {
+            warn!("Ignoring {what} of unknown module \"{module_name}\"");
+        }
+        module_id
+    }
+
+    /// Find a module by its name from the Indico CSV or its displayed name
+    ///
+    /// Exact matches are preferred. Otherwise, names are compared after
+    /// normalization with [`normalize_name()`], which ignores case and
+    /// differences in whitespace.
+    pub fn module_id(&self, module_name: &str) -> Option<ModuleId> {
+        let module_name = module_name.trim();
+        self.modules
+            .iter()
+            .position(|module| &*module.indico_name == module_name || &*module.name == module_name)
+            .or_else(|| {
+                let module_name = normalize_name(module_name);
+                self.modules.iter().position(|module| {
+                    normalize_name(&module.indico_name) == module_name
+                        || normalize_name(&module.name) == module_name
+                })
+            })
+    }
+
+    /// People who registered to at least one of some modules, in input order
+    ///
+    /// Without deduplication, people who registered several times with the
+    /// same email address are only listed once, at their first registration.
+    pub fn registered_to_any(&self, modules: &[ModuleId]) -> Vec<PersonId> {
+        let mut emails = HashSet::new();
+        (0..self.persons.len())
+            .filter(|&person_id| {
+                let person = &self.persons[person_id];
+                modules
+                    .iter()
+                    .any(|module_id| person.choice_of_modules.contains(module_id))
+                    && emails.insert(normalize_email(&person.identity.email_address))
+            })
+            .collect()
+    }
+
+    /// People who registered to every one of some modules, in input order
+    ///
+    /// Without deduplication, people who registered several times with the
+    /// same email address are only listed once, at their first matching
+    /// registration.
+    pub fn registered_to_all(&self, modules: &[ModuleId]) -> Vec<PersonId> {
+        let mut emails = HashSet::new();
+        (0..self.persons.len())
+            .filter(|&person_id| {
+                let person = &self.persons[person_id];
+                modules
+                    .iter()
+                    .all(|module_id| person.choice_of_modules.contains(module_id))
+                    && emails.insert(normalize_email(&person.identity.email_address))
+            })
+            .collect()
+    }
+
+    /// Find someone by email address
+    ///
+    /// Email addresses are compared after normalization. If several people
+    /// share an email address, which can happen without deduplication, the
+    /// first one is returned.
+    pub fn find_person(&self, email: &str) -> Option<&Person> {
+        self.find_person_id(email)
+            .map(|person_id| &self.persons[person_id])
+    }
+
+    /// Find the identifier of someone by email address, like
+    /// [`Registrations::find_person()`]
+    pub fn find_person_id(&self, email: &str) -> Option<PersonId> {
+        let email = normalize_email(email);
+        self.persons
+            .iter()
+            .position(|person| normalize_email(&person.identity.email_address) == email)
+    }
+
+    /// Modules that someone chose, in the order where they chose them
+    pub fn person_modules(&self, person_id: PersonId) -> impl Iterator<Item = &Module> {
+        self.persons[person_id]
+            .choice_of_modules
+            .iter()
+            .map(|&module_id| &self.modules[module_id])
+    }
+
+    /// Distinct non-empty affiliations that no simplification rule matched
+    ///
+    /// Each affiliation comes with the number of people who have it, and
+    /// the most common affiliations come first.
+    pub fn unsimplified_affiliations(&self) -> Vec<(&str, usize)> {
+        let mut counts = HashMap::<&str, usize>::new();
+        for person in &self.persons {
+            let identity = &person.identity;
+            if !identity.affiliation.is_empty() && identity.simplify_affiliation().is_none() {
+                *counts.entry(&identity.affiliation).or_default() += 1;
+            }
+        }
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        counts.sort_by_key(|&(affiliation, count)| (Reverse(count), affiliation));
+        counts
+    }
+
+    /// People whose registration time is outside of a plausible window, in
+    /// input order
+    pub fn out_of_range_registrations(&self, window: RegistrationWindow) -> Vec<PersonId> {
+        (0..self.persons.len())
+            .filter(|&person_id| !window.contains(self.persons[person_id].registration_time))
+            .collect()
+    }
+
+    /// Cohort of each person, indexed by [`PersonId`]
+    ///
+    /// People are split into tertiles of registration time, ties being
+    /// broken by order of appearance in the Indico CSV. When the number of
+    /// people isn't a multiple of 3, earlier cohorts are the larger ones.
+    pub fn registration_cohorts(&self) -> Vec<Cohort> {
+        let mut persons_by_time = (0..self.persons.len()).collect::<Vec<PersonId>>();
+        persons_by_time
+            .sort_by_key(|&person_id| (self.persons[person_id].registration_time, person_id));
+        let mut cohorts = vec![Cohort::Early; self.persons.len()];
+        for (rank, person_id) in persons_by_time.into_iter().enumerate() {
+            cohorts[person_id] = match rank * 3 / self.persons.len() {
+                0 => Cohort::Early,
+                1 => Cohort::Mid,
+                _ => Cohort::Late,
+            };
+        }
+        cohorts
+    }
+
+    /// The `count` people who chose the most modules, most modules first
+    ///
+    /// People who chose the same number of modules are ordered by
+    /// registration time.
+    pub fn top_registrants(&self, count: usize) -> Vec<PersonId> {
+        let mut persons = (0..self.persons.len()).collect::<Vec<PersonId>>();
+        persons.sort_by_key(|&person_id| {
+            let person = &self.persons[person_id];
+            (
+                Reverse(person.choice_of_modules.len()),
+                person.registration_time,
+                person_id,
+            )
+        });
+        persons.truncate(count);
+        persons
+    }
+
+    /// Number of people who registered to each module, indexed by ModuleId
+    pub fn registration_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.modules.len()];
+        for person in &self.persons {
+            for &module_id in &person.choice_of_modules {
+                counts[module_id] += 1;
+            }
+        }
+        counts
+    }
+
+    /// Record a data quality warning
+    pub fn warn(&mut self, warning: DataWarning) {
+        warn!("{warning}");
+        self.warnings.push(warning);
+    }
+
+    /// Find groups of people who have the same name but different emails
+    ///
+    /// These may be the same person registering twice with different email
+    /// addresses, which should be reviewed manually. Groups are ordered by
+    /// normalized name, and people within a group by input order.
+    pub fn possible_duplicates(&self) -> Vec<Vec<PersonId>> {
+        let mut name_to_persons = BTreeMap::<Box<str>, Vec<PersonId>>::new();
+        for (person_id, person) in self.persons.iter().enumerate() {
+            name_to_persons
+                .entry(normalize_name(&person.identity.name))
+                .or_default()
+                .push(person_id);
+        }
+        name_to_persons
+            .into_values()
+            .filter_map(|mut persons| {
+                let mut emails = HashSet::new();
+                persons.retain(|&person_id| {
+                    emails.insert(normalize_email(
+                        &self.persons[person_id].identity.email_address,
+                    ))
+                });
+                (persons.len() > 1).then_some(persons)
+            })
+            .collect()
+    }
+
+    /// Ordered list of people who registered to each module
+    ///
+    /// The result is indexed by [`ModuleId`]. See
+    /// [`Person::registration_order()`] for the ordering of people and the
+    /// meaning of `rounding`.
+    pub fn module_rosters(&self, rounding: Option<TimeUnit>) -> Vec<Vec<PersonId>> {
+        let persons_by_registration_order = self
+            .persons
+            .iter()
+            .enumerate()
+            .map(|(person_id, person)| (person.registration_order(person_id, rounding), person_id))
+            .collect::<BTreeMap<_, PersonId>>();
+        if log::max_level() >= log::Level::Debug {
+            debug!("People ordered by registration priority and time");
+            for ((Reverse(priority), date, _, _), person_id) in &persons_by_registration_order {
+                debug!(
+                    "- {} ({}, priority {})",
+                    self.persons[*person_id].identity, date, priority
+                );
+            }
+        }
+        let mut rosters = vec![Vec::new(); self.modules.len()];
+        for person_id in persons_by_registration_order.into_values() {
+            for &module_id in &self.persons[person_id].choice_of_modules {
+                rosters[module_id].push(person_id);
+            }
+        }
+        rosters
+    }
+
+    /// People who registered to each module, in input order
+    ///
+    /// This is like [`Registrations::module_rosters()`], but ignores
+    /// registration priorities and times.
+    pub fn input_order_rosters(&self) -> Vec<Vec<PersonId>> {
+        let mut rosters = vec![Vec::new(); self.modules.len()];
+        for (person_id, person) in self.persons.iter().enumerate() {
+            for &module_id in &person.choice_of_modules {
+                rosters[module_id].push(person_id);
+            }
+        }
+        rosters
+    }
+
+    /// Modules chosen by each person, ordered by start time
+    ///
+    /// People are listed in input order. Modules which start at the same
+    /// time (including modules whose start time is unknown) are ordered by
+    /// name, as in [`Registrations::modules_by_start_time()`].
+    pub fn person_schedules(&self) -> Vec<(PersonId, Vec<ModuleId>)> {
+        self.persons
+            .iter()
+            .enumerate()
+            .map(|(person_id, person)| {
+                let mut schedule = person.choice_of_modules.clone();
+                schedule.sort_by_key(|&module_id| (self.modules[module_id].slot, module_id));
+                (person_id, schedule)
+            })
+            .collect()
+    }
+
+    /// Find duplicate record IDs, and gaps larger than `max_gap` between
+    /// consecutive IDs, in increasing ID order
+    pub fn id_anomalies(&self, max_gap: usize) -> Vec<IdAnomaly> {
+        let mut id_counts = BTreeMap::<usize, usize>::new();
+        for &id in &self.record_ids {
+            *id_counts.entry(id).or_default() += 1;
+        }
+        let mut anomalies = Vec::new();
+        let mut previous_id = None;
+        for (id, count) in id_counts {
+            if let Some(previous_id) = previous_id {
+                if id - previous_id > max_gap {
+                    anomalies.push(IdAnomaly::Gap {
+                        after: previous_id,
+                        before: id,
+                    });
+                }
+            }
+            if count > 1 {
+                anomalies.push(IdAnomaly::Duplicate { id, count });
+            }
+            previous_id = Some(id);
+        }
+        anomalies
+    }
+
+    /// People grouped by simplified affiliation
+    ///
+    /// Affiliations are ordered by name, ignoring case, and people whose
+    /// affiliation is unknown come last under an empty affiliation. Within
+    /// each affiliation, people are ordered by registration time.
+    pub fn persons_by_affiliation(&self) -> Vec<(&str, Vec<PersonId>)> {
+        let mut groups = HashMap::<&str, Vec<PersonId>>::new();
+        for (person_id, person) in self.persons.iter().enumerate() {
+            groups
+                .entry(person.identity.simplified_affiliation().trim())
+                .or_default()
+                .push(person_id);
+        }
+        let mut groups = groups.into_iter().collect::<Vec<_>>();
+        groups.sort_by_cached_key(|(affiliation, _)| {
+            (
+                affiliation.is_empty(),
+                affiliation.to_lowercase(),
+                *affiliation,
+            )
+        });
+        for (_, persons) in &mut groups {
+            persons
+                .sort_by_key(|&person_id| (self.persons[person_id].registration_time, person_id));
+        }
+        groups
+    }
+
+    /// People who chose some modules, but none of the displayed ones
+    ///
+    /// This tells who vanishes from the output when only some modules are
+    /// displayed. People are ordered by registration time.
+    pub fn persons_outside(&self, displayed_modules: &[ModuleId]) -> Vec<PersonId> {
+        let displayed = displayed_modules.iter().copied().collect::<HashSet<_>>();
+        let mut hidden = (self.persons.iter().enumerate())
+            .filter(|(_, person)| {
+                !person.choice_of_modules.is_empty()
+                    && !(person.choice_of_modules.iter()).any(|module| displayed.contains(module))
+            })
+            .map(|(person_id, _)| person_id)
+            .collect::<Vec<_>>();
+        hidden.sort_by_key(|&person_id| (self.persons[person_id].registration_time, person_id));
+        hidden
+    }
+
+    /// Find days where people registered to more than `max_per_day` modules
+    ///
+    /// Overloads are ordered like [`Registrations::person_schedules()`], then
+    /// by date. Modules whose start time is unknown can't be attributed to a
+    /// day, so they are skipped with a warning.
+    pub fn daily_overloads(&self, max_per_day: usize) -> Vec<DailyOverload> {
+        for module in self.modules.iter().filter(|module| !module.is_scheduled()) {
+            warn!(
+                "Module \"{}\" has no known date, it will be ignored by the daily overload check",
+                module.indico_name
+            );
+        }
+        let mut overloads = Vec::new();
+        for (person_id, schedule) in self.person_schedules() {
+            let mut modules_by_date = BTreeMap::<Date, Vec<ModuleId>>::new();
+            for module_id in schedule {
+                let module = &self.modules[module_id];
+                if module.is_scheduled() {
+                    modules_by_date
+                        .entry(module.start_time.date())
+                        .or_default()
+                        .push(module_id);
+                }
+            }
+            overloads.extend(
+                modules_by_date
+                    .into_iter()
+                    .filter(|(_date, modules)| modules.len() > max_per_day)
+                    .map(|(date, modules)| DailyOverload {
+                        person_id,
+                        date,
+                        modules,
+                    }),
+            );
+        }
+        overloads
+    }
+
+    /// Find people who registered to a module after it started
+    ///
+    /// Module start times don't have a time zone, so they are compared with
+    /// the local registration time in the time zone used by Indico. Modules
+    /// whose start time is unknown are skipped. Late registrations are
+    /// ordered like [`Registrations::person_schedules()`].
+    pub fn late_registrations(&self) -> Vec<(PersonId, ModuleId)> {
+        self.person_schedules()
+            .into_iter()
+            .flat_map(|(person_id, schedule)| {
+                let person = &self.persons[person_id];
+                let local_registration_time = PrimitiveDateTime::new(
+                    person.registration_time.date(),
+                    person.registration_time.time(),
+                );
+                schedule
+                    .into_iter()
+                    .filter(move |&module_id| {
+                        let module = &self.modules[module_id];
+                        module.is_scheduled()
+                            && local_registration_time
+                                > PrimitiveDateTime::new(
+                                    module.start_time.date(),
+                                    module.start_time.time(),
+                                )
+                    })
+                    .map(move |module_id| (person_id, module_id))
+            })
+            .collect()
+    }
+
+    /// Compare these registrations with an older snapshot of them
+    ///
+    /// Modules are matched by displayed name and people by normalized email.
+    /// Modules of this snapshot come first, ordered by start time, followed by
+    /// modules which only exist in the old snapshot. Modules whose
+    /// registrants didn't change are skipped.
+    pub fn diff_from(&self, old: &Registrations) -> Vec<ModuleDiff> {
+        let registrant_emails = |registrations: &Registrations| {
+            let mut emails = HashMap::<Rc<str>, HashSet<Box<str>>>::new();
+            for module in &registrations.modules {
+                emails.entry(module.name.clone()).or_default();
+            }
+            for person in &registrations.persons {
+                for &module_id in &person.choice_of_modules {
+                    emails
+                        .entry(registrations.modules[module_id].name.clone())
+                        .or_default()
+                        .insert(normalize_email(&person.identity.email_address));
+                }
+            }
+            emails
+        };
+        let old_emails = registrant_emails(old);
+        let new_emails = registrant_emails(self);
+        let module_names = self
+            .modules_by_start_time()
+            .into_iter()
+            .map(|module_id| self.modules[module_id].name.clone())
+            .chain(
+                old.modules_by_start_time()
+                    .into_iter()
+                    .map(|module_id| old.modules[module_id].name.clone())
+                    .filter(|name| !new_emails.contains_key(name)),
+            );
+        let empty = HashSet::new();
+        module_names
+            .filter_map(|module| {
+                let old_emails = old_emails.get(&module).unwrap_or(&empty);
+                let new_emails = new_emails.get(&module).unwrap_or(&empty);
+                let sorted_difference = |a: &HashSet<Box<str>>, b: &HashSet<Box<str>>| {
+                    let mut emails = a.difference(b).cloned().collect::<Vec<_>>();
+                    emails.sort_unstable();
+                    emails
+                };
+                let diff = ModuleDiff {
+                    added: sorted_difference(new_emails, old_emails),
+                    removed: sorted_difference(old_emails, new_emails),
+                    module,
+                };
+                (!diff.added.is_empty() || !diff.removed.is_empty()).then_some(diff)
+            })
+            .collect()
+    }
+
+    /// List of all modules, ordered by start time slot, then by name
+    pub fn modules_by_start_time(&self) -> Vec<ModuleId> {
+        let mut modules = (0..self.modules.len()).collect::<Vec<ModuleId>>();
+        modules.sort_by_key(|&module_id| (self.modules[module_id].slot, module_id));
+        modules
+    }
+
+    /// List of all modules, ordered by start time, with unscheduled modules
+    /// ordered according to a canonical module list
+    ///
+    /// Scheduled modules are ordered as in
+    /// [`Registrations::modules_by_start_time()`]. Unscheduled modules from
+    /// `canonical_order` are placed as directed by `placement`, and those that
+    /// are not listed come last, ordered by name.
+    pub fn modules_by_canonical_order(
+        &self,
+        canonical_order: &[ModuleId],
+        placement: UnscheduledPlacement,
+    ) -> Vec<ModuleId> {
+        // Attach each listed unscheduled module to the scheduled module that
+        // precedes it in the list, if any
+        let mut attached = HashMap::<Option<ModuleId>, Vec<ModuleId>>::new();
+        let mut previous_scheduled = None;
+        let mut listed = HashSet::new();
+        for &module_id in canonical_order {
+            if !listed.insert(module_id) {
+                continue;
+            }
+            if self.modules[module_id].is_scheduled() {
+                previous_scheduled = Some(module_id);
+            } else {
+                let anchor = match placement {
+                    UnscheduledPlacement::Interleave => previous_scheduled,
+                    UnscheduledPlacement::Last => None,
+                };
+                attached.entry(anchor).or_default().push(module_id);
+            }
+        }
+
+        // Insert them after their anchor in the start time order
+        let modules_by_start_time = self.modules_by_start_time();
+        let (scheduled, unlisted): (Vec<_>, Vec<_>) = modules_by_start_time
+            .into_iter()
+            .filter(|module_id| {
+                self.modules[*module_id].is_scheduled() || !listed.contains(module_id)
+            })
+            .partition(|&module_id| self.modules[module_id].is_scheduled());
+        let mut result = Vec::with_capacity(self.modules.len());
+        if placement == UnscheduledPlacement::Interleave {
+            result.extend(attached.remove(&None).unwrap_or_default());
+        }
+        for module_id in scheduled {
+            result.push(module_id);
+            result.extend(attached.remove(&Some(module_id)).unwrap_or_default());
+        }
+        result.extend(attached.remove(&None).unwrap_or_default());
+        result.extend(unlisted);
+        result
+    }
+
+    /// Translate raw Indico records into a more exploitable form
+    ///
+    /// See [`RegistrationsBuilder`] for more ways to load registrations.
+    pub fn new(raw_records: Vec<CSVRecord>, options: ProcessingOptions) -> Self {
+        RegistrationsBuilder::new()
+            .options(options)
+            .build(raw_records.into_iter().map(Ok))
+            .expect("Records that are already loaded can't fail to load")
+    }
+
+    /// Like [`Registrations::new()`], but processes records as they come
+    ///
+    /// This works with [`read_raw_records()`], so that the raw records never
+    /// need to be all in memory at the same time. Errors from the record
+    /// iterator are propagated as soon as they are encountered.
+    ///
+    /// [`read_raw_records()`]: crate::input::read_raw_records
+    pub fn try_new(
+        raw_records: impl IntoIterator<Item = csv::Result<CSVRecord>>,
+        options: ProcessingOptions,
+    ) -> csv::Result<Self> {
+        debug!("Post-processing registration records...");
+        let mut result = Self::default();
+        let mut module_to_id = HashMap::<Box<str>, ModuleId>::new();
+        let mut email_to_person = HashMap::<Box<str>, PersonId>::new();
+        let mut reported_emails = HashSet::new();
+        for raw_record in raw_records {
+            let CSVRecord {
+                id,
+                identity,
+                choice_of_modules,
+                registration_time,
+                priority,
+                registration_state,
+                ..
+            } = raw_record?;
+            if let Some(filter) = &options.affiliation_filter {
+                if !filter.is_match(&identity.affiliation) {
+                    debug!("- Skipping {identity}, whose affiliation is filtered out");
+                    continue;
+                }
+            }
+            if !is_plausible_email(&identity.email_address) {
+                result.warn(DataWarning::InvalidEmail {
+                    name: identity.name.clone(),
+                    email: identity.email_address.clone(),
+                });
+            }
+            let module_names = split_module_choices(&choice_of_modules);
+            let chose_no_module = module_names.is_empty();
+            let module_ids = module_names
+                .iter()
+                .map(String::as_str)
+                .filter(|module_name| {
+                    (options.module_filter.as_ref()).is_none_or(|filter| filter.is_match(module_name))
+                })
+                .map(|module_name| {
+                    // Have we seen this module before, maybe with different
+                    // internal whitespace?
+                    let key = collapse_whitespace(module_name);
+                    let module_id = if let Some(&module_id) = module_to_id.get(&key) {
+                        // Reuse previous module ID
+                        let known_name = &result.modules[module_id].indico_name;
+                        if **known_name != *module_name {
+                            debug!(
+                                "- Merged module \"{module_name}\" into \"{known_name}\", as they only differ by whitespace"
+                            );
+                        }
+                        module_id
+                    } else {
+                        // Post-process module name, deduce module ID
+                        let module = Module::new(module_name, &options);
+                        if !module.is_scheduled() && !options.is_unparsed(module_name) {
+                            result.warn(DataWarning::UnparsedModuleTime {
+                                module: module_name.into(),
+                            });
+                        }
+                        let module_id = result.modules.len();
+                        result.modules.push(module);
+                        module_to_id.insert(key, module_id);
+                        module_id
+                    };
+                    module_id
+                })
+                .collect::<Vec<_>>();
+            result.num_records += 1;
+            result.record_ids.extend(id);
+            if chose_no_module {
+                result.warn(DataWarning::EmptyChoiceOfModules {
+                    name: identity.name.clone(),
+                    email: identity.email_address.clone(),
+                });
+            }
+
+            // Detect duplicate registrations, merge them if requested
+            let email = normalize_email(&identity.email_address);
+            if let Some(&person_id) = email_to_person.get(&email) {
+                if reported_emails.insert(email.clone()) {
+                    result.warn(DataWarning::DuplicateEmail {
+                        email: email.clone(),
+                        merged: options.dedup,
+                    });
+                }
+                if options.dedup {
+                    let person = &mut result.persons[person_id];
+                    debug!("- Merging duplicate registration from {}", person.identity);
+                    for module_id in module_ids {
+                        if !person.choice_of_modules.contains(&module_id) {
+                            person.choice_of_modules.push(module_id);
+                        }
+                    }
+                    person.registration_time = person.registration_time.min(registration_time);
+                    if let Some(priority) = priority {
+                        person.priority = person.priority.max(priority);
+                    }
+                    if person.registration_state.is_none() {
+                        person.registration_state = registration_state;
+                    }
+                    result.num_merged_duplicates += 1;
+                    continue;
+                }
+            } else {
+                email_to_person.insert(email, result.persons.len());
+            }
+            result.persons.push(Person {
+                identity,
+                choice_of_modules: module_ids,
+                registration_time,
+                priority: priority.unwrap_or(0),
+                registration_state,
+            })
+        }
+        if let Some(min_similarity) = options.fuzzy_modules {
+            result.merge_similar_modules(min_similarity);
+        }
+        result.warn_about_misspelled_modules();
+        for unparsed in &options.unparsed_modules {
+            let unparsed = collapse_whitespace(unparsed);
+            if !module_to_id.contains_key(&unparsed) {
+                warn!("Module \"{unparsed}\", whose start time should not be parsed, is not in the CSV");
+            }
+        }
+        if !options.preserve_input_order {
+            result.sort_modules_by_name();
+        }
+        Ok(result)
+    }
+
+    /// Merge modules with similar names, see
+    /// [`ProcessingOptions::fuzzy_modules`]
+    fn merge_similar_modules(&mut self, min_similarity: f64) {
+        let counts = self.registration_counts();
+        let names = self
+            .modules
+            .iter()
+            .map(|module| module.indico_name.to_lowercase())
+            .collect::<Vec<_>>();
+
+        // Let the most popular modules absorb the similar modules first
+        let mut by_popularity = (0..self.modules.len()).collect::<Vec<ModuleId>>();
+        by_popularity.sort_by_key(|&module_id| (Reverse(counts[module_id]), module_id));
+        let mut canonical = (0..self.modules.len()).collect::<Vec<ModuleId>>();
+        let mut absorbed = vec![false; self.modules.len()];
+        for (rank, &module_id) in by_popularity.iter().enumerate() {
+            if absorbed[module_id] {
+                continue;
+            }
+            let cluster = by_popularity[rank + 1..]
+                .iter()
+                .copied()
+                .filter(|&other_id| {
+                    !absorbed[other_id]
+                        && self.modules[other_id].slot == self.modules[module_id].slot
+                        && strsim::normalized_damerau_levenshtein(
+                            &names[module_id],
+                            &names[other_id],
+                        ) >= min_similarity
+                })
+                .collect::<Vec<_>>();
+            if cluster.is_empty() {
+                continue;
+            }
+            for &other_id in &cluster {
+                absorbed[other_id] = true;
+                canonical[other_id] = module_id;
+            }
+            warn!(
+                "Merged modules {} into \"{}\", as their names are similar",
+                cluster
+                    .iter()
+                    .map(|&other_id| format!("\"{}\"", self.modules[other_id].indico_name))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                self.modules[module_id].indico_name
+            );
+        }
+
+        // Forget about the warnings on absorbed modules, which are gone
+        let absorbed_names = (0..self.modules.len())
+            .filter(|&module_id| absorbed[module_id])
+            .map(|module_id| &*self.modules[module_id].indico_name)
+            .collect::<HashSet<_>>();
+        self.warnings.retain(|warning| match warning {
+            DataWarning::UnparsedModuleTime { module } => !absorbed_names.contains(&**module),
+            _ => true,
+        });
+
+        // Drop the absorbed modules and renumber the others
+        let mut new_ids = vec![0; self.modules.len()];
+        let mut num_kept = 0;
+        for (module_id, &absorbed) in absorbed.iter().enumerate() {
+            if !absorbed {
+                new_ids[module_id] = num_kept;
+                num_kept += 1;
+            }
+        }
+        let mut module_ids = 0..;
+        self.modules
+            .retain(|_| !absorbed[module_ids.next().expect("Range is infinite")]);
+        for person in &mut self.persons {
+            let mut choice_of_modules = Vec::with_capacity(person.choice_of_modules.len());
+            for &module_id in &person.choice_of_modules {
+                let module_id = new_ids[canonical[module_id]];
+                if !choice_of_modules.contains(&module_id) {
+                    choice_of_modules.push(module_id);
+                }
+            }
+            person.choice_of_modules = choice_of_modules;
+        }
+    }
+
+    /// Warn about modules which look like a misspelling of another module
+    ///
+    /// Since every module name that appears in the CSV is treated as a
+    /// module, a misspelled module choice ends up as a separate module. Such a
+    /// module is detected when it has a very similar name to a module that
+    /// starts at the same time and was chosen by more people. Modules whose
+    /// start time is unknown are skipped, as they could be anything.
+    fn warn_about_misspelled_modules(&mut self) {
+        const MIN_SIMILARITY: f64 = 0.85;
+        let counts = self.registration_counts();
+        let names = self
+            .modules
+            .iter()
+            .map(|module| module.indico_name.to_lowercase())
+            .collect::<Vec<_>>();
+        for module_id in 0..self.modules.len() {
+            if !self.modules[module_id].is_scheduled() {
+                continue;
+            }
+            let suggestion = (0..self.modules.len())
+                .filter(|&other_id| {
+                    counts[other_id] > counts[module_id]
+                        && self.modules[other_id].is_scheduled()
+                        && self.modules[other_id].slot == self.modules[module_id].slot
+                })
+                .map(|other_id| {
+                    let similarity =
+                        strsim::normalized_damerau_levenshtein(&names[module_id], &names[other_id]);
+                    (similarity, other_id)
+                })
+                .filter(|&(similarity, _)| similarity >= MIN_SIMILARITY)
+                .max_by(|(similarity1, _), (similarity2, _)| similarity1.total_cmp(similarity2));
+            if let Some((_, suggestion_id)) = suggestion {
+                self.warn(DataWarning::PossiblyMisspelledModule {
+                    module: (*self.modules[module_id].indico_name).into(),
+                    suggestion: (*self.modules[suggestion_id].indico_name).into(),
+                });
+            }
+        }
+    }
+
+    /// Renumber modules in the order of their Indico names
+    ///
+    /// Module IDs are initially assigned in the order where modules are
+    /// first seen in the CSV. Renumbering them makes the output independent
+    /// of the order of CSV rows, even where it depends on module IDs (e.g.
+    /// ordering of modules with the same start time).
+    pub fn sort_modules_by_name(&mut self) {
+        let mut old_ids = (0..self.modules.len()).collect::<Vec<ModuleId>>();
+        old_ids.sort_by(|&id1, &id2| {
+            self.modules[id1]
+                .indico_name
+                .cmp(&self.modules[id2].indico_name)
+        });
+        let mut new_ids = vec![0; old_ids.len()];
+        for (new_id, &old_id) in old_ids.iter().enumerate() {
+            new_ids[old_id] = new_id;
+        }
+        self.modules
+            .sort_by(|module1, module2| module1.indico_name.cmp(&module2.indico_name));
+        for person in &mut self.persons {
+            for module_id in &mut person.choice_of_modules {
+                *module_id = new_ids[*module_id];
+            }
+        }
+    }
+}
+
+/// Split Indico's "Choice of modules" field into module names
+///
+/// Module names are separated by `;`. A module name which contains a
+/// semicolon must escape it as `\;`, which is turned back into `;` after
+/// splitting. Surrounding whitespace and empty module names are removed.
+pub fn split_module_choices(choice_of_modules: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut chars = choice_of_modules.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&';') => {
+                current.push(';');
+                chars.next();
+            }
+            ';' => result.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    result.push(current);
+    result
+        .into_iter()
+        .map(|module_name| module_name.trim().to_owned())
+        .filter(|module_name| !module_name.is_empty())
+        .collect()
+}
+
+/// Normalize an email address for comparison purposes
+pub fn normalize_email(email: &str) -> Box<str> {
+    email.trim().to_lowercase().into()
+}
+
+/// Truth that a string looks like a valid email address
+///
+/// This only catches obvious mistakes, like missing or duplicate `@`, a
+/// domain without a dot, or stray whitespace.
+pub fn is_plausible_email(email: &str) -> bool {
+    let email = email.trim();
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !email.contains(char::is_whitespace)
+}
+
+/// Collapse runs of whitespace into single spaces
+///
+/// This is used to identify modules, so that module names that only differ by
+/// internal whitespace designate the same module.
+fn collapse_whitespace(text: &str) -> Box<str> {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").into()
+}
+
+/// Normalize a person name for comparison purposes
+pub fn normalize_name(name: &str) -> Box<str> {
+    name.nfc()
+        .collect::<String>()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .into()
+}
@@ -0,0 +1,284 @@
+//! Tests of the admission of registered people to modules
+
+use indico_transpose::{
+    admission::{Capacity, Limits, Roster, WaitlistReason},
+    input::{read_raw_records, ColumnMapping},
+    registrations::{ProcessingOptions, Registrations},
+};
+
+/// Header of the Indico CSV export
+const HEADER: &str =
+    "ID,Name,Email Address,Affiliation,Choice of modules,Registration date,Registration state";
+
+/// Post-process registration records from the rows of an Indico CSV export
+fn load(rows: &[&str]) -> Registrations {
+    let csv = std::iter::once(HEADER)
+        .chain(rows.iter().copied())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let records = read_raw_records(
+        csv::Reader::from_reader(csv.as_bytes()),
+        &ColumnMapping::default(),
+    )
+    .expect("Fixture should be valid");
+    Registrations::try_new(records, ProcessingOptions::default()).expect("Fixture should be valid")
+}
+
+/// Names of some people, in order
+fn names(registrations: &Registrations, persons: impl IntoIterator<Item = usize>) -> Vec<&str> {
+    persons
+        .into_iter()
+        .map(|person_id| &*registrations.persons[person_id].identity.name)
+        .collect()
+}
+
+/// People over their affiliation's quota are waitlisted, even if the module
+/// still has free seats
+#[test]
+fn affiliation_quota() {
+    let registrations = load(&[
+        r#"1,Alice,alice@example.org,LAL,"Rust intro",2024-02-01 10:00:00.0+01:00,Complete"#,
+        r#"2,Bob,bob@example.org,LAL,"Rust intro",2024-02-02 10:00:00.0+01:00,Complete"#,
+        r#"3,Carol,carol@example.org,IJCLab,"Rust intro",2024-02-03 10:00:00.0+01:00,Complete"#,
+    ]);
+    let mut limits = Limits::default();
+    limits.affiliation_quotas.insert((0, "LAL".into()), 1);
+    let rosters = limits.admit_all(&registrations, registrations.module_rosters(None));
+    assert_eq!(
+        names(&registrations, rosters[0].accepted.iter().copied()),
+        ["Alice", "Carol"]
+    );
+    assert_eq!(
+        rosters[0].waitlisted,
+        [(1, WaitlistReason::AffiliationQuota)]
+    );
+}
+
+/// People who are on the waiting list in Indico stay there, whatever their
+/// registration time and the free seats
+#[test]
+fn waitlist_state() {
+    let registrations = load(&[
+        r#"1,Alice,alice@example.org,LAL,"Rust intro",2024-02-01 10:00:00.0+01:00,Waiting list"#,
+        r#"2,Bob,bob@example.org,LAL,"Rust intro",2024-02-02 10:00:00.0+01:00,Complete"#,
+        r#"3,Carol,carol@example.org,IJCLab,"Rust intro",2024-02-03 10:00:00.0+01:00,Complete"#,
+    ]);
+    let limits = Limits {
+        waitlist_state: Some("waiting LIST".into()),
+        ..Default::default()
+    };
+    let rosters = limits.admit_all(&registrations, registrations.module_rosters(None));
+    assert_eq!(
+        names(&registrations, rosters[0].accepted.iter().copied()),
+        ["Bob", "Carol"]
+    );
+    assert_eq!(
+        rosters[0].waitlisted,
+        [(0, WaitlistReason::RegistrationState)]
+    );
+
+    // The state is ignored if no waitlist state is configured
+    let rosters = Limits::default().admit_all(&registrations, registrations.module_rosters(None));
+    assert_eq!(rosters[0].accepted, [0, 1, 2]);
+}
+
+#[test]
+fn percentage_capacity() {
+    assert_eq!(
+        Capacity::parse_override("Rust intro=50%"),
+        Ok(("Rust intro".into(), Capacity::Percentage(50.0)))
+    );
+    assert_eq!(
+        Capacity::parse_override("a=b=3"),
+        Ok(("a=b".into(), Capacity::Seats(3)))
+    );
+    assert!(Capacity::parse_override("Rust intro").is_err());
+    assert!(Capacity::parse_override("Rust intro=-5%").is_err());
+
+    // Percentages are rounded down, but leave at least one seat
+    let half = Capacity::Percentage(50.0);
+    assert_eq!(half.num_seats(4), 2);
+    assert_eq!(half.num_seats(5), 2);
+    assert_eq!(half.num_seats(7), 3);
+    assert_eq!(half.num_seats(1), 1);
+    assert_eq!(Capacity::Percentage(10.0).num_seats(3), 1);
+}
+
+/// A capacity of 50% accepts half of the registrants
+#[test]
+fn half_capacity_module() {
+    let registrations = load(&[
+        r#"1,Alice,alice@example.org,LAL,"Rust intro",2024-02-01 10:00:00.0+01:00,Complete"#,
+        r#"2,Bob,bob@example.org,LAL,"Rust intro",2024-02-02 10:00:00.0+01:00,Complete"#,
+        r#"3,Carol,carol@example.org,IJCLab,"Rust intro",2024-02-03 10:00:00.0+01:00,Complete"#,
+        r#"4,David,david@example.org,IJCLab,"Rust intro",2024-02-04 10:00:00.0+01:00,Complete"#,
+    ]);
+    let mut limits = Limits::default();
+    limits.capacities.insert(0, Capacity::parse("50%").unwrap());
+    let rosters = limits.admit_all(&registrations, registrations.module_rosters(None));
+    assert_eq!(rosters[0].accepted, [0, 1]);
+    assert_eq!(
+        rosters[0].waitlisted,
+        [(2, WaitlistReason::Capacity), (3, WaitlistReason::Capacity)]
+    );
+}
+
+/// With fair rotation, people who were waitlisted in an oversubscribed module
+/// get priority in the next one
+#[test]
+fn fair_rotation() {
+    let choices = "Python basics 15/03, 09h00; Rust intro 15/03, 14h00";
+    let rows = ["Alice", "Bob", "Carol", "David"]
+        .into_iter()
+        .enumerate()
+        .map(|(idx, name)| {
+            format!(
+                "{},{name},{}@example.org,LAL,\"{choices}\",2024-02-0{} 10:00:00.0+01:00,Complete",
+                idx + 1,
+                name.to_lowercase(),
+                idx + 1
+            )
+        })
+        .collect::<Vec<_>>();
+    let registrations = load(&rows.iter().map(String::as_str).collect::<Vec<_>>());
+    let python = registrations
+        .module_id("Python basics 15/03, 09h00")
+        .unwrap();
+    let rust = registrations.module_id("Rust intro 15/03, 14h00").unwrap();
+    let mut limits = Limits::default();
+    limits.capacities.insert(python, Capacity::Seats(2));
+    limits.capacities.insert(rust, Capacity::Seats(2));
+
+    // Without fair rotation, the earliest registrants get every seat
+    let rosters = limits.admit_all(&registrations, registrations.module_rosters(None));
+    assert_eq!(
+        names(&registrations, rosters[python].accepted.iter().copied()),
+        ["Alice", "Bob"]
+    );
+    assert_eq!(
+        names(&registrations, rosters[rust].accepted.iter().copied()),
+        ["Alice", "Bob"]
+    );
+
+    // With fair rotation, Carol and David get the seats of the second module
+    limits.fair_rotation = Some(1);
+    let rosters = limits.admit_all(&registrations, registrations.module_rosters(None));
+    assert_eq!(
+        names(&registrations, rosters[python].accepted.iter().copied()),
+        ["Alice", "Bob"]
+    );
+    assert_eq!(
+        names(&registrations, rosters[rust].accepted.iter().copied()),
+        ["Carol", "David"]
+    );
+    assert_eq!(
+        names(
+            &registrations,
+            rosters[rust]
+                .waitlisted
+                .iter()
+                .map(|&(person_id, _)| person_id)
+        ),
+        ["Alice", "Bob"]
+    );
+}
+
+/// When respecting choice order, oversubscribed modules favor the people who
+/// ranked them higher, while other modules keep the registration order
+#[test]
+fn respect_choice_order() {
+    let registrations = load(&[
+        r#"1,Carol,carol@example.org,LAL,"Python basics 15/03, 09h00; Rust intro 15/03, 14h00",2024-02-01 10:00:00.0+01:00,Complete"#,
+        r#"2,Alice,alice@example.org,LAL,"Rust intro 15/03, 14h00; Python basics 15/03, 09h00",2024-02-02 10:00:00.0+01:00,Complete"#,
+        r#"3,Bob,bob@example.org,LAL,"Python basics 15/03, 09h00; Rust intro 15/03, 14h00",2024-02-03 10:00:00.0+01:00,Complete"#,
+    ]);
+    let python = registrations
+        .module_id("Python basics 15/03, 09h00")
+        .unwrap();
+    let rust = registrations.module_id("Rust intro 15/03, 14h00").unwrap();
+    let mut limits = Limits::default();
+    limits.capacities.insert(python, Capacity::Seats(2));
+    let waitlisted = |roster: &Roster| {
+        (roster.waitlisted.iter())
+            .map(|&(person_id, _)| person_id)
+            .collect::<Vec<_>>()
+    };
+
+    // By default, registration order decides
+    let rosters = limits.admit_all(&registrations, registrations.module_rosters(None));
+    assert_eq!(
+        names(&registrations, rosters[python].accepted.iter().copied()),
+        ["Carol", "Alice"]
+    );
+    assert_eq!(names(&registrations, waitlisted(&rosters[python])), ["Bob"]);
+
+    // Bob ranked Python first, unlike Alice, so he gets her seat
+    limits.respect_choice_order = true;
+    let rosters = limits.admit_all(&registrations, registrations.module_rosters(None));
+    assert_eq!(
+        names(&registrations, rosters[python].accepted.iter().copied()),
+        ["Carol", "Bob"]
+    );
+    assert_eq!(
+        names(&registrations, waitlisted(&rosters[python])),
+        ["Alice"]
+    );
+    assert_eq!(
+        names(&registrations, rosters[rust].accepted.iter().copied()),
+        ["Carol", "Alice", "Bob"]
+    );
+}
+
+/// People whose state is an alias of "cancelled" are not listed at all
+#[test]
+fn cancelled_state_alias() {
+    let registrations = load(&[
+        r#"1,Alice,alice@example.org,LAL,"Rust intro",2024-02-01 10:00:00.0+01:00,withdrawn"#,
+        r#"2,Bob,bob@example.org,LAL,"Rust intro",2024-02-02 10:00:00.0+01:00,Pending"#,
+        r#"3,Carol,carol@example.org,IJCLab,"Rust intro",2024-02-03 10:00:00.0+01:00,Approved"#,
+    ]);
+    let mut limits = Limits::default();
+    limits.capacities.insert(0, Capacity::Seats(1));
+    limits
+        .load_state_aliases(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/state-aliases.csv"
+        ))
+        .expect("Fixture should be valid");
+    let rosters = limits.admit_all(&registrations, registrations.module_rosters(None));
+    assert_eq!(
+        names(&registrations, rosters[0].accepted.iter().copied()),
+        ["Carol"]
+    );
+    assert_eq!(
+        rosters[0].waitlisted,
+        [(1, WaitlistReason::RegistrationState)]
+    );
+}
+
+/// Percentage capacities are computed from the people whose registration was
+/// not cancelled, and the resulting seat count is kept in the roster
+#[test]
+fn percentage_capacity_ignores_cancelled() {
+    let registrations = load(&[
+        r#"1,Alice,alice@example.org,LAL,"Rust intro",2024-02-01 10:00:00.0+01:00,withdrawn"#,
+        r#"2,Bob,bob@example.org,LAL,"Rust intro",2024-02-02 10:00:00.0+01:00,withdrawn"#,
+        r#"3,Carol,carol@example.org,IJCLab,"Rust intro",2024-02-03 10:00:00.0+01:00,Approved"#,
+        r#"4,David,david@example.org,IJCLab,"Rust intro",2024-02-04 10:00:00.0+01:00,Approved"#,
+    ]);
+    let mut limits = Limits::default();
+    limits.capacities.insert(0, Capacity::Percentage(50.0));
+    limits
+        .load_state_aliases(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/state-aliases.csv"
+        ))
+        .expect("Fixture should be valid");
+    let rosters = limits.admit_all(&registrations, registrations.module_rosters(None));
+    assert_eq!(rosters[0].num_seats, Some(1));
+    assert_eq!(
+        names(&registrations, rosters[0].accepted.iter().copied()),
+        ["Carol"]
+    );
+    assert_eq!(rosters[0].waitlisted, [(3, WaitlistReason::Capacity)]);
+}
@@ -0,0 +1,59 @@
+//! Tests of the precedence of affiliation simplification rules
+//!
+//! Rules can only be installed once per process, so everything is checked
+//! from a single test.
+
+use indico_transpose::input::{simplify_affiliation, AffiliationRules};
+use regex::Regex;
+
+#[test]
+fn rule_precedence() {
+    // Built-in rules apply before any rule is installed
+    assert_eq!(
+        simplify_affiliation("IJCLAB - IN2P3 - CNRS"),
+        Some("IJCLab")
+    );
+
+    let mut rules = AffiliationRules::default();
+    rules
+        .exact
+        .insert("IJCLAB - IN2P3 - CNRS".into(), "IJCLab (exact)".into());
+    rules
+        .exact
+        .insert("Université Paris-Saclay".into(), "UPS (exact)".into());
+    rules
+        .regexes
+        .push((Regex::new("^IJCLab.*$").unwrap(), "IJCLab (regex)".into()));
+    rules.regexes.push((
+        Regex::new("^Université.*$").unwrap(),
+        "Université (regex)".into(),
+    ));
+    rules
+        .normalized
+        .insert("IJCLab - IN2P3 - CNRS".into(), "IJCLab (normalized)".into());
+    rules
+        .normalized
+        .insert("LAL".into(), "LAL (normalized)".into());
+    rules.install();
+
+    // Exact matches beat regexes, which beat built-in rules, which beat the
+    // normalizer
+    assert_eq!(
+        simplify_affiliation("IJCLAB - IN2P3 - CNRS"),
+        Some("IJCLab (exact)")
+    );
+    assert_eq!(
+        simplify_affiliation("Université Paris-Saclay"),
+        Some("UPS (exact)")
+    );
+    assert_eq!(
+        simplify_affiliation("IJCLab - IN2P3 - CNRS"),
+        Some("IJCLab (regex)")
+    );
+    assert_eq!(
+        simplify_affiliation("Université Paris Cité"),
+        Some("Université (regex)")
+    );
+    assert_eq!(simplify_affiliation("LAL"), Some("LAL (normalized)"));
+    assert_eq!(simplify_affiliation("CERN"), None);
+}
@@ -0,0 +1,161 @@
+//! Tests of module start time parsing
+
+use indico_transpose::registrations::{Module, NameLayout, ProcessingOptions};
+use time::{Month, Time};
+
+/// Processing options with month name parsing enabled
+fn month_names() -> ProcessingOptions {
+    ProcessingOptions {
+        month_names: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn english_month_name() {
+    let module = Module::new("Rust intro 15 March, 14h00", &month_names());
+    assert!(module.is_scheduled());
+    assert_eq!(module.start_time.month(), Month::March);
+    assert_eq!(module.start_time.day(), 15);
+    assert_eq!(module.start_time.time(), Time::from_hms(14, 0, 0).unwrap());
+}
+
+#[test]
+fn french_month_name() {
+    let module = Module::new("Initiation à Rust 15 mars, 14h00", &month_names());
+    assert!(module.is_scheduled());
+    assert_eq!(module.start_time.month(), Month::March);
+    assert_eq!(module.start_time.day(), 15);
+    assert_eq!(module.start_time.time(), Time::from_hms(14, 0, 0).unwrap());
+}
+
+#[test]
+fn month_names_are_opt_in() {
+    let module = Module::new("Rust intro 15 March, 14h00", &ProcessingOptions::default());
+    assert!(!module.is_scheduled());
+}
+
+#[test]
+fn month_name_stripped_from_name() {
+    let options = ProcessingOptions {
+        strip_date_from_name: true,
+        ..month_names()
+    };
+    let module = Module::new("Deep Learning 16 mars, 09:30", &options);
+    assert_eq!(&*module.name, "Deep Learning");
+}
+
+#[test]
+fn unparsed_module() {
+    let options = ProcessingOptions {
+        strip_date_from_name: true,
+        unparsed_modules: vec!["Rust  news 03/15, 14h00".into()],
+        ..Default::default()
+    };
+    let exempted = Module::new("Rust news 03/15, 14h00", &options);
+    assert!(!exempted.is_scheduled());
+    assert_eq!(&*exempted.name, "Rust news 03/15, 14h00");
+    let other = Module::new("Rust intro 03/15, 14h00", &options);
+    assert!(other.is_scheduled());
+    assert_eq!(&*other.name, "Rust intro");
+}
+
+#[test]
+fn iso_datetime() {
+    let module = Module::new("Workshop 2025-03-15T14:00", &ProcessingOptions::default());
+    assert!(module.is_scheduled());
+    assert_eq!(module.start_time.year(), 2025);
+    assert_eq!(module.start_time.month(), Month::March);
+    assert_eq!(module.start_time.day(), 15);
+    assert_eq!(module.start_time.time(), Time::from_hms(14, 0, 0).unwrap());
+}
+
+#[test]
+fn iso_datetime_stripped_from_name() {
+    let options = ProcessingOptions {
+        strip_date_from_name: true,
+        ..Default::default()
+    };
+    let module = Module::new("Workshop 2024-03-15 09:30:00Z", &options);
+    assert_eq!(&*module.name, "Workshop");
+    assert_eq!(module.start_time.time(), Time::from_hms(9, 30, 0).unwrap());
+}
+
+#[test]
+fn date_stripped_from_name() {
+    let options = ProcessingOptions {
+        strip_date_from_name: true,
+        ..Default::default()
+    };
+    let module = Module::new("Rust 2024 intro 15/03, 14h00", &options);
+    assert_eq!(&*module.name, "Rust 2024 intro");
+    assert_eq!(&*module.indico_name, "Rust 2024 intro 15/03, 14h00");
+    assert_eq!(module.start_time.month(), Month::March);
+    assert_eq!(module.start_time.day(), 15);
+    assert_eq!(module.start_time.time(), Time::from_hms(14, 0, 0).unwrap());
+
+    // Names are left alone by default
+    let module = Module::new(
+        "Rust 2024 intro 15/03, 14h00",
+        &ProcessingOptions::default(),
+    );
+    assert_eq!(&*module.name, "Rust 2024 intro 15/03, 14h00");
+}
+
+#[test]
+fn name_layouts() {
+    let layout = |name_layout| ProcessingOptions {
+        strip_date_from_name: true,
+        name_layout: Some(name_layout),
+        ..Default::default()
+    };
+    let module = Module::new(
+        "Rust intro - 15/03, 14h00 (room B)",
+        &layout(NameLayout::DateSuffix),
+    );
+    assert_eq!(&*module.name, "Rust intro");
+    let module = Module::new(
+        "[15/03, 14h00] - Rust intro",
+        &layout(NameLayout::DatePrefix),
+    );
+    assert_eq!(&*module.name, "Rust intro");
+    assert_eq!(module.start_time.day(), 15);
+    assert_eq!(module.start_time.month(), Month::March);
+}
+
+/// Modules a minute apart share a time slot with a 5 minute granularity, but
+/// keep their own start time
+#[test]
+fn time_slot_granularity() {
+    let options = ProcessingOptions {
+        module_time_granularity: Some(5),
+        ..Default::default()
+    };
+    let first = Module::new("Rust intro 15/03, 14h00", &options);
+    let second = Module::new("Python basics 15/03, 14h01", &options);
+    let later = Module::new("Deep Learning 15/03, 14h03", &options);
+    assert_eq!(first.slot, second.slot);
+    assert_ne!(first.slot, later.slot);
+    assert_eq!(second.start_time.time(), Time::from_hms(14, 1, 0).unwrap());
+
+    // Without a granularity, every minute is its own slot
+    let first = Module::new("Rust intro 15/03, 14h00", &ProcessingOptions::default());
+    let second = Module::new("Python basics 15/03, 14h01", &ProcessingOptions::default());
+    assert_ne!(first.slot, second.slot);
+}
+
+/// Schedules with an invalid date or time leave the module unscheduled
+#[test]
+fn invalid_schedules() {
+    for name in [
+        "Workshop 31 february, 10h00",
+        "Workshop 15 march, 25h00",
+        "Workshop 31/02, 10h00",
+        "Workshop 15/00, 10h00",
+        "Workshop 15/03, 10h75",
+    ] {
+        let module = Module::new(name, &month_names());
+        assert!(!module.is_scheduled(), "{name} should not be scheduled");
+        assert_eq!(&*module.name, name);
+    }
+}
@@ -0,0 +1,107 @@
+//! Tests of output formatting helpers
+
+use indico_transpose::{
+    input::Identity,
+    output::{write_atomically, DisplayOptions, IdentityFormat, Locale, Markup},
+};
+
+/// Identity of someone whose name and affiliation need escaping in HTML
+fn identity() -> Identity {
+    Identity {
+        name: "Alice <Al> Durand".into(),
+        email_address: "alice@example.org".into(),
+        affiliation: "R&D".into(),
+    }
+}
+
+#[test]
+fn english_counts() {
+    let strings = Locale::En.strings();
+    assert_eq!(Locale::En.count(0, strings.person), "0 persons");
+    assert_eq!(Locale::En.count(1, strings.person), "1 person");
+    assert_eq!(Locale::En.count(2, strings.module), "2 modules");
+}
+
+#[test]
+fn french_counts() {
+    let strings = Locale::Fr.strings();
+    assert_eq!(Locale::Fr.count(0, strings.person), "0 personne");
+    assert_eq!(Locale::Fr.count(1, strings.person), "1 personne");
+    assert_eq!(Locale::Fr.count(2, strings.person), "2 personnes");
+}
+
+#[test]
+fn identity_markup() {
+    let identity = identity();
+    let format = IdentityFormat::default();
+    assert_eq!(
+        identity.render(Markup::Markdown, format),
+        "`Alice <Al> Durand <alice@example.org>` from R&D"
+    );
+    assert_eq!(
+        identity.render(Markup::Plain, format),
+        "Alice <Al> Durand <alice@example.org> from R&D"
+    );
+    assert_eq!(
+        identity.render(Markup::Html, format),
+        "<code>Alice &lt;Al&gt; Durand &lt;alice@example.org&gt;</code> from R&amp;D"
+    );
+}
+
+#[test]
+fn identity_formats() {
+    let identity = identity();
+    let render = |format| identity.render(Markup::Plain, format);
+    assert_eq!(
+        render(IdentityFormat::NameEmailAffiliation),
+        "Alice <Al> Durand <alice@example.org> from R&D"
+    );
+    assert_eq!(render(IdentityFormat::NameOnly), "Alice <Al> Durand");
+    assert_eq!(
+        render(IdentityFormat::NameAffiliation),
+        "Alice <Al> Durand from R&D"
+    );
+    assert_eq!(
+        render(IdentityFormat::NameEmail),
+        "Alice <Al> Durand <alice@example.org>"
+    );
+}
+
+/// Module names are truncated on character boundaries, ellipsis included
+#[test]
+fn truncated_module_names() {
+    let options = DisplayOptions {
+        truncate_names: Some(4),
+        ..Default::default()
+    };
+    assert_eq!(options.module_name("Électronique numérique"), "Éle…");
+    assert_eq!(options.module_name("Été"), "Été");
+    assert_eq!(options.module_name("Œuvre"), "Œuv…");
+    let options = DisplayOptions::default();
+    assert_eq!(
+        options.module_name("Électronique numérique"),
+        "Électronique numérique"
+    );
+}
+
+/// Atomic writes replace the destination without leaving a temporary file
+#[test]
+fn atomic_write() {
+    let dir = std::env::temp_dir().join(format!("indico-transpose-{}-atomic", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("rosters.md");
+    std::fs::write(&path, "Old rosters").unwrap();
+    write_atomically(&path, "New rosters").unwrap();
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let files = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect::<Vec<_>>();
+
+    // Writing into a missing directory is an error
+    let missing_dir = dir.join("missing").join("rosters.md");
+    assert!(write_atomically(&missing_dir, "Lost rosters").is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(contents, "New rosters");
+    assert_eq!(files, ["rosters.md"]);
+}
@@ -0,0 +1,651 @@
+//! Tests of the post-processing of registration records
+
+use indico_transpose::{
+    input::{load_raw_records, read_raw_records, ColumnMapping},
+    registrations::{
+        split_module_choices, Cohort, DataWarning, ProcessingOptions, RegistrationWindow,
+        Registrations, RegistrationsBuilder, TimeUnit,
+    },
+};
+use regex::Regex;
+use time::macros::{datetime, offset};
+
+/// Header of the Indico CSV export
+const HEADER: &str =
+    "ID,Name,Email Address,Affiliation,Choice of modules,Registration date,Registration state";
+
+/// Post-process registration records from the rows of an Indico CSV export
+fn load(rows: &[&str]) -> Registrations {
+    try_load(rows, &ColumnMapping::default()).expect("Fixture should be valid")
+}
+
+/// Like [`load()`], with a custom column mapping, and without assuming that
+/// the records are valid
+fn try_load(rows: &[&str], columns: &ColumnMapping) -> csv::Result<Registrations> {
+    let csv = std::iter::once(HEADER)
+        .chain(rows.iter().copied())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let records = read_raw_records(csv::Reader::from_reader(csv.as_bytes()), columns)?;
+    Registrations::try_new(records, ProcessingOptions::default())
+}
+
+/// Module names that only differ by internal whitespace are merged
+#[test]
+fn whitespace_only_module_differences() {
+    let registrations = load(&[
+        r#"1,Alice Durand,alice@example.org,LAL,"Deep  Learning 03/16, 09:30",2024-02-01 10:00:00+01:00,Complete"#,
+        r#"2,Bob Martin,bob@example.org,LAL,"Deep Learning 03/16, 09:30",2024-02-02 10:00:00+01:00,Complete"#,
+    ]);
+    assert_eq!(registrations.modules.len(), 1);
+    assert_eq!(
+        &*registrations.modules[0].indico_name,
+        "Deep  Learning 03/16, 09:30"
+    );
+    assert_eq!(
+        registrations.persons[0].choice_of_modules,
+        registrations.persons[1].choice_of_modules
+    );
+}
+
+/// Module choices can be given as a JSON array
+#[test]
+fn json_module_choices() {
+    let columns = ColumnMapping {
+        modules_json: true,
+        ..Default::default()
+    };
+    let registrations = try_load(
+        &[
+            r#"1,Alice Durand,alice@example.org,LAL,"[""Rust intro 03/15, 14h00"", ""Deep Learning; theory 03/16, 09:30""]",2024-02-01 10:00:00+01:00,Complete"#,
+        ],
+        &columns,
+    )
+    .expect("Fixture should be valid");
+    let mut modules = registrations
+        .modules
+        .iter()
+        .map(|module| &*module.indico_name)
+        .collect::<Vec<_>>();
+    modules.sort_unstable();
+    assert_eq!(
+        modules,
+        [
+            "Deep Learning; theory 03/16, 09:30",
+            "Rust intro 03/15, 14h00"
+        ]
+    );
+
+    let error = try_load(
+        &[r#"1,Alice Durand,alice@example.org,LAL,"Rust intro 03/15, 14h00",2024-02-01 10:00:00+01:00,Complete"#],
+        &columns,
+    )
+    .expect_err("Module choices are not JSON");
+    assert!(error.to_string().contains("not a JSON array"), "{error}");
+}
+
+/// The builder reads out CSV exports, and only merges duplicates on request
+#[test]
+fn builder_without_dedup() {
+    let csv = [
+        HEADER,
+        r#"1,Alice Durand,alice@example.org,LAL,"Rust intro 03/15, 14h00",2024-02-01 10:00:00+01:00,Complete"#,
+        r#"2,Alice Durand,Alice@example.org,LAL,"Deep Learning 03/16, 09:30",2024-02-02 10:00:00+01:00,Complete"#,
+    ]
+    .join("\n");
+    let registrations = RegistrationsBuilder::new()
+        .dedup(false)
+        .read([csv.as_bytes()])
+        .expect("Fixture should be valid");
+    assert_eq!(registrations.num_records, 2);
+    assert_eq!(registrations.persons.len(), 2);
+    assert_eq!(registrations.num_merged_duplicates, 0);
+
+    let registrations = RegistrationsBuilder::new()
+        .dedup(true)
+        .read([csv.as_bytes()])
+        .expect("Fixture should be valid");
+    assert_eq!(registrations.persons.len(), 1);
+    assert_eq!(registrations.num_merged_duplicates, 1);
+}
+
+/// The builder sets the year of module schedules, and filters modules and
+/// affiliations while loading
+#[test]
+fn builder_year_and_filters() {
+    let csv = [
+        HEADER,
+        r#"1,Alice Durand,alice@example.org,LAL,"Rust intro 03/15, 14h00; Deep Learning 03/16, 09:30",2024-02-01 10:00:00+01:00,Complete"#,
+        r#"2,Bob Martin,bob@example.org,IJCLab,"Rust intro 03/15, 14h00",2024-02-02 10:00:00+01:00,Complete"#,
+    ]
+    .join("\n");
+    let registrations = RegistrationsBuilder::new()
+        .year(2025)
+        .module_filter(Some(Regex::new("^Rust").unwrap()))
+        .affiliation_filter(Some(Regex::new("^LAL$").unwrap()))
+        .read([csv.as_bytes()])
+        .expect("Fixture should be valid");
+    assert_eq!(registrations.num_records, 1);
+    assert_eq!(&*registrations.persons[0].identity.name, "Alice Durand");
+    assert_eq!(registrations.persons[0].choice_of_modules, [0]);
+    assert_eq!(registrations.modules.len(), 1);
+    assert_eq!(registrations.modules[0].start_time.year(), 2025);
+    assert!(registrations.warnings.is_empty());
+}
+
+/// Registration time parsing is configured per builder, so that records can
+/// be loaded several times with different settings
+#[test]
+fn builder_registration_times() {
+    let csv = [
+        HEADER,
+        r#"1,Alice Durand,alice@example.org,LAL,"Rust intro 03/15, 14h00",2024-02-01 10:00:00,Complete"#,
+        r#"2,Bob Martin,bob@example.org,LAL,"Rust intro 03/15, 14h00",2024-02-02,Complete"#,
+    ]
+    .join("\n");
+    let registrations = RegistrationsBuilder::new()
+        .assume_offset(offset!(+02:00))
+        .allow_date_only(true)
+        .read([csv.as_bytes()])
+        .expect("Fixture should be valid");
+    let times = (registrations.persons.iter())
+        .map(|person| person.registration_time)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        times,
+        [
+            datetime!(2024-02-01 10:00:00 +02:00),
+            datetime!(2024-02-02 00:00:00 +02:00)
+        ]
+    );
+    assert!(RegistrationsBuilder::new().read([csv.as_bytes()]).is_err());
+}
+
+/// Modules with similar names can be merged into the most popular one
+#[test]
+fn fuzzy_module_merging() {
+    let csv = [
+        HEADER,
+        r#"1,Alice Durand,alice@example.org,LAL,"Deep Learning; Rust intro",2024-02-01 10:00:00+01:00,Complete"#,
+        r#"2,Bob Martin,bob@example.org,LAL,"Deep Learnning",2024-02-02 10:00:00+01:00,Complete"#,
+        r#"3,Carol Petit,carol@example.org,LAL,"Deep Learning",2024-02-03 10:00:00+01:00,Complete"#,
+    ]
+    .join("\n");
+    let records = read_raw_records(
+        csv::Reader::from_reader(csv.as_bytes()),
+        &ColumnMapping::default(),
+    )
+    .unwrap();
+    let registrations = RegistrationsBuilder::new()
+        .fuzzy_modules(Some(0.9))
+        .build(records)
+        .unwrap();
+    let names = (registrations.modules.iter())
+        .map(|module| &*module.indico_name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Deep Learning", "Rust intro"]);
+    for person in &registrations.persons {
+        assert!(person.choice_of_modules.contains(&0));
+    }
+
+    // Merging is opt-in
+    let registrations = load(&csv.lines().skip(1).collect::<Vec<_>>());
+    assert_eq!(registrations.modules.len(), 3);
+}
+
+/// Warnings about modules that were merged into another one are dropped
+#[test]
+fn fuzzy_merging_drops_absorbed_warnings() {
+    let csv = [
+        HEADER,
+        r#"1,Alice Durand,alice@example.org,LAL,"Mystery module",2024-02-01 10:00:00+01:00,Complete"#,
+        r#"2,Bob Martin,bob@example.org,LAL,"Mystery modul",2024-02-02 10:00:00+01:00,Complete"#,
+        r#"3,Carol Petit,carol@example.org,LAL,"Mystery module",2024-02-03 10:00:00+01:00,Complete"#,
+    ]
+    .join("\n");
+    let records = read_raw_records(
+        csv::Reader::from_reader(csv.as_bytes()),
+        &ColumnMapping::default(),
+    )
+    .unwrap();
+    let registrations = RegistrationsBuilder::new()
+        .fuzzy_modules(Some(0.9))
+        .build(records)
+        .unwrap();
+    assert_eq!(registrations.modules.len(), 1);
+    assert_eq!(
+        registrations.warnings,
+        [DataWarning::UnparsedModuleTime {
+            module: "Mystery module".into()
+        }]
+    );
+}
+
+/// A higher priority beats an earlier registration time
+#[test]
+fn priority_order() {
+    let csv = [
+        &format!("{HEADER},Priority"),
+        r#"1,Alice Durand,alice@example.org,LAL,"Rust intro",2024-02-01 10:00:00+01:00,Complete,"#,
+        r#"2,Bob Martin,bob@example.org,LAL,"Rust intro",2024-02-02 10:00:00+01:00,Complete,0"#,
+        r#"3,Carol Petit,carol@example.org,LAL,"Rust intro",2024-02-03 10:00:00+01:00,Complete,5"#,
+    ]
+    .join("\n");
+    let registrations = RegistrationsBuilder::new()
+        .columns(ColumnMapping {
+            priority: Some("Priority".into()),
+            ..Default::default()
+        })
+        .read([csv.as_bytes()])
+        .expect("Fixture should be valid");
+    let roster = &registrations.module_rosters(None)[0];
+    let names = (roster.iter())
+        .map(|&person_id| &*registrations.persons[person_id].identity.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Carol Petit", "Alice Durand", "Bob Martin"]);
+
+    // Without the priority column, registration time decides
+    let registrations = RegistrationsBuilder::new()
+        .read([csv.as_bytes()])
+        .expect("Fixture should be valid");
+    assert_eq!(registrations.module_rosters(None)[0], [0, 1, 2]);
+}
+
+/// People with the same name but different emails are flagged, not merged
+#[test]
+fn same_name_different_emails() {
+    let registrations = load(&[
+        r#"1,Alice Durand,alice@example.org,LAL,"Rust intro",2024-02-01 10:00:00+01:00,Complete"#,
+        r#"2,Bob Martin,bob@example.org,LAL,"Rust intro",2024-02-02 10:00:00+01:00,Complete"#,
+        r#"3,alice  DURAND,adurand@example.com,LAL,"Rust intro",2024-02-03 10:00:00+01:00,Complete"#,
+    ]);
+    assert_eq!(registrations.persons.len(), 3);
+    assert_eq!(registrations.possible_duplicates(), [vec![0, 2]]);
+}
+
+/// Registrations within the same rounded time unit are ordered by email
+#[test]
+fn minute_tie() {
+    let registrations = load(&[
+        r#"1,Zoe Martin,zoe@example.org,LAL,"Rust intro",2024-02-01 10:00:05+01:00,Complete"#,
+        r#"2,Alice Durand,alice@example.org,LAL,"Rust intro",2024-02-01 10:00:55+01:00,Complete"#,
+        r#"3,Aaron Petit,aaron@example.org,LAL,"Rust intro",2024-02-01 10:01:00+01:00,Complete"#,
+    ]);
+    assert_eq!(registrations.module_rosters(None)[0], [0, 1, 2]);
+    assert_eq!(
+        registrations.module_rosters(Some(TimeUnit::Minute))[0],
+        [1, 0, 2]
+    );
+}
+
+/// Each person's modules come out in start time order
+#[test]
+fn person_schedules() {
+    let registrations = load(&[
+        r#"1,Alice Durand,alice@example.org,LAL,"Deep Learning 16/03, 09:30; Rust intro 15/03, 14h00; Python basics 15/03, 09h00",2024-02-01 10:00:00+01:00,Complete"#,
+        r#"2,Bob Martin,bob@example.org,LAL,"Rust intro 15/03, 14h00",2024-02-02 10:00:00+01:00,Complete"#,
+    ]);
+    let schedules = registrations.person_schedules();
+    assert_eq!(schedules.len(), 2);
+    let (person_id, schedule) = &schedules[0];
+    assert_eq!(*person_id, 0);
+    let names = (schedule.iter())
+        .map(|&module_id| &*registrations.modules[module_id].indico_name)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "Python basics 15/03, 09h00",
+            "Rust intro 15/03, 14h00",
+            "Deep Learning 16/03, 09:30"
+        ]
+    );
+}
+
+/// People registered to too many modules on one day are reported
+#[test]
+fn daily_overload() {
+    let registrations = load(&[
+        r#"1,Alice Durand,alice@example.org,LAL,"Python basics 15/03, 09h00; Rust intro 15/03, 14h00; Git 15/03, 16h00; Deep Learning 16/03, 09:30",2024-02-01 10:00:00+01:00,Complete"#,
+        r#"2,Bob Martin,bob@example.org,LAL,"Rust intro 15/03, 14h00; Git 15/03, 16h00; Mystery module",2024-02-02 10:00:00+01:00,Complete"#,
+    ]);
+    let overloads = registrations.daily_overloads(2);
+    assert_eq!(overloads.len(), 1);
+    let overload = &overloads[0];
+    assert_eq!(overload.person_id, 0);
+    assert_eq!(overload.date.day(), 15);
+    let names = (overload.modules.iter())
+        .map(|&module_id| &*registrations.modules[module_id].indico_name)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "Python basics 15/03, 09h00",
+            "Rust intro 15/03, 14h00",
+            "Git 15/03, 16h00"
+        ]
+    );
+}
+
+/// Escaped semicolons are part of a module name, not module separators
+#[test]
+fn escaped_semicolon() {
+    let registrations = load(&[
+        r#"1,Alice Durand,alice@example.org,LAL,"Q\; A 15/03, 14h00; Rust intro 15/03, 16h00",2024-02-01 10:00:00+01:00,Complete"#,
+    ]);
+    let mut names = registrations
+        .modules
+        .iter()
+        .map(|module| &*module.indico_name)
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+    assert_eq!(names, ["Q; A 15/03, 14h00", "Rust intro 15/03, 16h00"]);
+}
+
+/// Only a backslash followed by a semicolon is an escape sequence
+#[test]
+fn split_escaped_choices() {
+    assert_eq!(
+        split_module_choices(r"A\;B ; C\D;; E\"),
+        ["A;B", r"C\D", r"E\"]
+    );
+}
+
+/// Streaming records into [`Registrations::try_new()`] gives the same result as
+/// loading them all first
+#[test]
+fn streaming_matches_batch() {
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/registrations.csv"
+    );
+    let reader = || csv::Reader::from_path(path).expect("Fixture should be readable");
+    let columns = ColumnMapping::default();
+    let batch = Registrations::new(
+        load_raw_records(reader(), &columns).expect("Fixture should be valid"),
+        ProcessingOptions::default(),
+    );
+    let streaming = Registrations::try_new(
+        read_raw_records(reader(), &columns).expect("Fixture should be valid"),
+        ProcessingOptions::default(),
+    )
+    .expect("Fixture should be valid");
+    assert_eq!(streaming.num_records, 5);
+    assert_eq!(format!("{streaming:?}"), format!("{batch:?}"));
+}
+
+/// Registrations after a module started are late, those before aren't
+#[test]
+fn late_registration() {
+    let registrations = load(&[
+        r#"1,Alice Durand,alice@example.org,LAL,"Rust intro 15/03, 14h00",2024-03-15 14:30:00+01:00,Complete"#,
+        r#"2,Bob Martin,bob@example.org,LAL,"Rust intro 15/03, 14h00",2024-03-15 13:30:00+01:00,Complete"#,
+        r#"3,Carol Petit,carol@example.org,LAL,"Mystery module",2024-03-20 10:00:00+01:00,Complete"#,
+    ]);
+    let late = registrations
+        .late_registrations()
+        .into_iter()
+        .map(|(person_id, module_id)| {
+            (
+                &*registrations.persons[person_id].identity.name,
+                &*registrations.modules[module_id].name,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(late, [("Alice Durand", "Rust intro 15/03, 14h00")]);
+}
+
+/// Column headers are recognized whatever their case
+#[test]
+fn case_insensitive_headers() {
+    let csv = "id,NAME,email address,AFFILIATION,choice of modules,Registration Date,registration state\n\
+               1,Alice Durand,alice@example.org,LAL,\"Rust intro 15/03, 14h00\",2024-02-01 10:00:00+01:00,Complete";
+    let records = read_raw_records(
+        csv::Reader::from_reader(csv.as_bytes()),
+        &ColumnMapping::default(),
+    )
+    .expect("Headers should be recognized");
+    let registrations = Registrations::try_new(records, ProcessingOptions::default())
+        .expect("Records should be valid");
+    assert_eq!(registrations.persons.len(), 1);
+    assert_eq!(
+        &*registrations.persons[0].identity.email_address,
+        "alice@example.org"
+    );
+    assert_eq!(registrations.record_ids, [1]);
+}
+
+/// People can be looked up by normalized email, along with their modules
+#[test]
+fn find_person() {
+    let registrations = load(&[
+        r#"1,Alice Durand,alice@example.org,LAL,"Rust intro 15/03, 14h00; Deep Learning 16/03, 09:30",2024-02-01 10:00:00+01:00,Complete"#,
+        r#"2,Bob Martin,bob@example.org,LAL,"Deep Learning 16/03, 09:30",2024-02-02 10:00:00+01:00,Complete"#,
+    ]);
+    let alice = registrations
+        .find_person(" Alice@Example.org ")
+        .expect("Alice should be found");
+    assert_eq!(&*alice.identity.name, "Alice Durand");
+    let person_id = registrations
+        .find_person_id("alice@example.org")
+        .expect("Alice should be found");
+    let modules = registrations
+        .person_modules(person_id)
+        .map(|module| &*module.name)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        modules,
+        ["Rust intro 15/03, 14h00", "Deep Learning 16/03, 09:30"]
+    );
+    assert!(registrations.find_person("carol@example.org").is_none());
+}
+
+/// Registration times are accepted with or without fractional seconds
+#[test]
+fn optional_subseconds() {
+    let registrations = load(&[
+        r#"1,Alice Durand,alice@example.org,LAL,"Rust intro 15/03, 14h00",2024-02-01 10:00:00.25+01:00,Complete"#,
+        r#"2,Bob Martin,bob@example.org,LAL,"Rust intro 15/03, 14h00",2024-02-01 10:00:00+01:00,Complete"#,
+    ]);
+    let [alice, bob] = [0, 1].map(|person_id| registrations.persons[person_id].registration_time);
+    assert_eq!(alice.millisecond(), 250);
+    assert_eq!(bob.millisecond(), 0);
+    assert_eq!(alice - bob, time::Duration::milliseconds(250));
+    assert_eq!(bob.offset(), time::UtcOffset::from_hms(1, 0, 0).unwrap());
+}
+
+/// Module choices can be spread over several columns, which may be empty
+#[test]
+fn module_columns() {
+    let csv = "ID,Name,Email Address,Affiliation,Morning module,Afternoon module,Registration date,Registration state\n\
+               1,Alice Durand,alice@example.org,LAL,\"Python basics 15/03, 09h00\",\"Rust intro 15/03, 14h00\",2024-02-01 10:00:00+01:00,Complete\n\
+               2,Bob Martin,bob@example.org,LAL,,\"Rust intro 15/03, 14h00\",2024-02-02 10:00:00+01:00,Complete";
+    let registrations = RegistrationsBuilder::new()
+        .columns(ColumnMapping {
+            modules: vec!["Morning module".into(), "Afternoon module".into()],
+            ..Default::default()
+        })
+        .read([csv.as_bytes()])
+        .expect("Fixture should be valid");
+    assert_eq!(registrations.modules.len(), 2);
+    let choices = (0..2)
+        .map(|person_id| {
+            registrations
+                .person_modules(person_id)
+                .map(|module| &*module.name)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        choices,
+        [
+            vec!["Python basics 15/03, 09h00", "Rust intro 15/03, 14h00"],
+            vec!["Rust intro 15/03, 14h00"]
+        ]
+    );
+}
+
+/// The top registrants chose the most modules, ties going to early registrants
+#[test]
+fn top_registrants() {
+    let registrations = load(&[
+        r#"1,Carol Petit,carol@example.org,LAL,"Rust intro 15/03, 14h00",2024-01-30 10:00:00+01:00,Complete"#,
+        r#"2,Alice Durand,alice@example.org,LAL,"Python basics 15/03, 09h00; Rust intro 15/03, 14h00; Deep Learning 16/03, 09:30",2024-02-03 10:00:00+01:00,Complete"#,
+        r#"3,David Leroy,david@example.org,LAL,"Python basics 15/03, 09h00; Rust intro 15/03, 14h00",2024-02-02 10:00:00+01:00,Complete"#,
+        r#"4,Bob Martin,bob@example.org,LAL,"Python basics 15/03, 09h00; Deep Learning 16/03, 09:30",2024-02-01 10:00:00+01:00,Complete"#,
+    ]);
+    let top = registrations
+        .top_registrants(2)
+        .into_iter()
+        .map(|person_id| &*registrations.persons[person_id].identity.name)
+        .collect::<Vec<_>>();
+    assert_eq!(top, ["Alice Durand", "Bob Martin"]);
+    assert_eq!(registrations.top_registrants(10).len(), 4);
+}
+
+/// People are split into thirds by registration time
+#[test]
+fn registration_cohorts() {
+    let rows = (1..=7)
+        .map(|day| {
+            format!(
+                r#"{day},Person {day},person{day}@example.org,LAL,"Rust intro 15/03, 14h00",2024-02-0{day} 10:00:00+01:00,Complete"#
+            )
+        })
+        .collect::<Vec<_>>();
+    let rows = rows.iter().map(String::as_str).collect::<Vec<_>>();
+    use Cohort::{Early, Late, Mid};
+
+    // Six people are evenly split, with their input order reversed
+    let mut six = rows[..6].to_vec();
+    six.reverse();
+    assert_eq!(
+        load(&six).registration_cohorts(),
+        [Late, Late, Mid, Mid, Early, Early]
+    );
+
+    // Otherwise, earlier cohorts are the larger ones
+    assert_eq!(
+        load(&rows[..4]).registration_cohorts(),
+        [Early, Early, Mid, Late]
+    );
+    assert_eq!(
+        load(&rows).registration_cohorts(),
+        [Early, Early, Early, Mid, Mid, Late, Late]
+    );
+}
+
+/// Registrations outside of the plausible window are reported, but kept
+#[test]
+fn out_of_range_registrations() {
+    let registrations = load(&[
+        r#"1,Alice Durand,alice@example.org,LAL,"Rust intro 15/03, 14h00",2024-02-01 10:00:00+01:00,Complete"#,
+        r#"2,Bob Martin,bob@example.org,LAL,"Rust intro 15/03, 14h00",9999-12-31 10:00:00+01:00,Complete"#,
+        r#"3,Carol Petit,carol@example.org,LAL,"Rust intro 15/03, 14h00",2024-03-31 23:30:00+01:00,Complete"#,
+    ]);
+    let window = RegistrationWindow::parse("2024-01-01..2024-03-31").unwrap();
+    assert_eq!(registrations.out_of_range_registrations(window), [1]);
+    assert_eq!(registrations.persons.len(), 3);
+    assert!(RegistrationWindow::parse("2024-03-31..2024-01-01").is_err());
+    assert!(RegistrationWindow::parse("2024-01-01").is_err());
+}
+
+/// A scheduled module chosen by fewer people than a similarly named module at
+/// the same time is a possible misspelling, but unscheduled ones are ignored
+#[test]
+fn misspelled_module() {
+    let registrations = load(&[
+        r#"1,Alice Durand,alice@example.org,LAL,"Rust intro 15/03, 14h00; Mystery module",2024-02-01 10:00:00+01:00,Complete"#,
+        r#"2,Bob Martin,bob@example.org,LAL,"Rust intro 15/03, 14h00; Mystery module",2024-02-02 10:00:00+01:00,Complete"#,
+        r#"3,Carol Petit,carol@example.org,LAL,"Rust intr 15/03, 14h00; Mystery modul",2024-02-03 10:00:00+01:00,Complete"#,
+    ]);
+    let misspellings = (registrations.warnings.iter())
+        .filter(|warning| matches!(warning, DataWarning::PossiblyMisspelledModule { .. }))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        misspellings,
+        [&DataWarning::PossiblyMisspelledModule {
+            module: "Rust intr 15/03, 14h00".into(),
+            suggestion: "Rust intro 15/03, 14h00".into(),
+        }]
+    );
+}
+
+/// Only people who chose every named module are registered to all of them
+#[test]
+fn registered_to_all() {
+    let registrations = load(&[
+        r#"1,Alice Durand,alice@example.org,LAL,"Rust intro 15/03, 14h00; Deep Learning 16/03, 09:30",2024-02-01 10:00:00+01:00,Complete"#,
+        r#"2,Bob Martin,bob@example.org,LAL,"Deep Learning 16/03, 09:30",2024-02-02 10:00:00+01:00,Complete"#,
+        r#"3,Carol Petit,carol@example.org,LAL,"Rust intro 15/03, 14h00; Python basics 15/03, 09h00",2024-02-03 10:00:00+01:00,Complete"#,
+    ]);
+    let modules = ["rust  intro 15/03, 14h00", "DEEP LEARNING 16/03, 09:30"].map(|name| {
+        registrations
+            .module_id(name)
+            .expect("Module names are normalized")
+    });
+    let names = registrations
+        .registered_to_all(&modules)
+        .into_iter()
+        .map(|person_id| &*registrations.persons[person_id].identity.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Alice Durand"]);
+
+    // People who registered twice are only listed once
+    let registrations = load(&[
+        r#"1,Alice Durand,alice@example.org,LAL,"Rust intro 15/03, 14h00; Deep Learning 16/03, 09:30",2024-02-01 10:00:00+01:00,Complete"#,
+        r#"2,Alice Durand,Alice@Example.org,LAL,"Rust intro 15/03, 14h00; Deep Learning 16/03, 09:30",2024-02-02 10:00:00+01:00,Complete"#,
+    ]);
+    let modules = ["Rust intro 15/03, 14h00", "Deep Learning 16/03, 09:30"]
+        .map(|name| registrations.module_id(name).unwrap());
+    assert_eq!(registrations.registered_to_all(&modules), [0]);
+}
+
+/// Sampling only loads the first records, across all inputs
+#[test]
+fn sample() {
+    let first = [
+        HEADER,
+        r#"1,Alice Durand,alice@example.org,LAL,"Rust intro 15/03, 14h00",2024-02-01 10:00:00+01:00,Complete"#,
+    ]
+    .join("\n");
+    let second = [
+        HEADER,
+        r#"2,Bob Martin,bob@example.org,LAL,"Rust intro 15/03, 14h00",2024-02-02 10:00:00+01:00,Complete"#,
+        r#"3,Carol Petit,carol@example.org,LAL,"Rust intro 15/03, 14h00",2024-02-03 10:00:00+01:00,Complete"#,
+    ]
+    .join("\n");
+    let registrations = RegistrationsBuilder::new()
+        .sample(Some(2))
+        .read([first.as_bytes(), second.as_bytes()])
+        .expect("Fixture should be valid");
+    assert_eq!(registrations.num_records, 2);
+    assert_eq!(registrations.record_ids, [1, 2]);
+    let registrations = RegistrationsBuilder::new()
+        .read([first.as_bytes(), second.as_bytes()])
+        .expect("Fixture should be valid");
+    assert_eq!(registrations.num_records, 3);
+}
+
+/// Modules with an invalid date are reported like other unparsed module times
+#[test]
+fn invalid_module_date() {
+    let csv = [
+        HEADER,
+        r#"1,Alice Durand,alice@example.org,LAL,"Workshop 31 february, 10h00",2024-02-01 10:00:00+01:00,Complete"#,
+    ]
+    .join("\n");
+    let registrations = RegistrationsBuilder::new()
+        .options(ProcessingOptions {
+            month_names: true,
+            ..Default::default()
+        })
+        .read([csv.as_bytes()])
+        .expect("Fixture should be valid");
+    assert_eq!(
+        registrations.warnings,
+        [DataWarning::UnparsedModuleTime {
+            module: "Workshop 31 february, 10h00".into()
+        }]
+    );
+}
@@ -0,0 +1,1195 @@
+//! End-to-end tests comparing the output of the binary to expected snapshots
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Directory containing the input fixtures and expected outputs
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+/// Run indico-transpose with some arguments, checking that it succeeds
+///
+/// Returns the standard output and the standard error.
+fn run(args: &[&str]) -> (String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_indico-transpose"))
+        .args(args)
+        .current_dir(FIXTURES)
+        .env("RUST_LOG", "warn")
+        .output()
+        .expect("Failed to run indico-transpose");
+    let stdout = String::from_utf8(output.stdout).expect("Output should be UTF-8");
+    let stderr = String::from_utf8(output.stderr).expect("Errors should be UTF-8");
+    assert!(
+        output.status.success(),
+        "indico-transpose failed:\n{stderr}"
+    );
+    (stdout, stderr)
+}
+
+/// Path of a temporary file that is specific to one test of this run
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("indico-transpose-{}-{name}", std::process::id()))
+}
+
+/// Check that some output matches an expected snapshot from the fixtures
+fn assert_snapshot(output: &str, snapshot: &str) {
+    let path = Path::new(FIXTURES).join(snapshot);
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read snapshot {} ({e})", path.display()));
+    assert!(
+        output == expected,
+        "Output does not match snapshot {}\n--- Expected ---\n{expected}\n--- Actual ---\n{output}",
+        path.display()
+    );
+}
+
+/// Modules are sorted by start time, people by registration date, and the
+/// IJCLab affiliations are simplified
+#[test]
+fn markdown_rosters() {
+    let (stdout, _) = run(&["registrations.csv"]);
+    assert_snapshot(&stdout, "registrations.md");
+}
+
+/// Same as [`markdown_rosters()`], with one table per module
+#[test]
+fn markdown_table_rosters() {
+    let (stdout, _) = run(&["registrations.csv", "--format", "markdown-table"]);
+    assert_snapshot(&stdout, "registrations-table.md");
+}
+
+/// Modules without a parseable start time come last, with a warning
+#[test]
+fn unparsed_module_time() {
+    let (stdout, stderr) = run(&["registrations.csv"]);
+    assert!(stdout
+        .trim_end()
+        .ends_with("## Mystery module\n\n1. `Carol Petit <carol@example.org>`"));
+    assert!(
+        stderr.contains("Couldn't parse start time of module \"Mystery module\""),
+        "Missing warning in:\n{stderr}"
+    );
+}
+
+/// Affiliations can be left out while keeping email addresses
+#[test]
+fn no_affiliation() {
+    let (stdout, _) = run(&["registrations.csv", "--no-affiliation"]);
+    assert!(stdout.contains("1. `David Leroy <david@example.org>`\n"));
+    assert!(
+        !stdout.contains("IJCLab"),
+        "Affiliation in output:\n{stdout}"
+    );
+    assert!(
+        !stdout.contains(" from "),
+        "Affiliation in output:\n{stdout}"
+    );
+}
+
+/// Registrants are grouped by affiliation, ordered by registration time
+#[test]
+fn by_affiliation() {
+    let (stdout, _) = run(&["registrations.csv", "--by-affiliation"]);
+    assert_snapshot(&stdout, "by-affiliation.md");
+}
+
+/// Counts in the summary header agree with their nouns
+#[test]
+fn summary_header() {
+    let (stdout, _) = run(&["registrations.csv", "--summary-header"]);
+    assert!(stdout.starts_with("5 persons across 4 modules\n"));
+    let (stdout, _) = run(&["registrations.csv", "--summary-header", "--locale", "fr"]);
+    assert!(stdout.starts_with("5 personnes réparties sur 4 modules\n"));
+}
+
+/// Modules above the capacity warning threshold are flagged before they fill up
+#[test]
+fn nearing_capacity() {
+    let (stdout, _) = run(&[
+        "nearing-capacity.csv",
+        "--capacities",
+        "capacities.csv",
+        "--capacity-warn-pct",
+        "80",
+    ]);
+    assert!(stdout.starts_with(
+        "# Nearing capacity\n\n- Deep Learning 03/16, 09:30: 9 registered, capacity 10 (90% full)\n\n#"
+    ));
+}
+
+/// Percentage capacities are computed without cancelled people, as during
+/// admission
+#[test]
+fn nearing_capacity_cancelled() {
+    let (stdout, _) = run(&[
+        "states.csv",
+        "--state-aliases",
+        "state-aliases.csv",
+        "--capacity",
+        "Rust intro 15/03, 14h00=120%",
+        "--capacity-warn-pct",
+        "80",
+    ]);
+    assert!(stdout.starts_with(
+        "# Nearing capacity\n\n- Rust intro 15/03, 14h00: 4 registered, capacity 4 (100% full)\n\n#"
+    ));
+}
+
+/// Paginated rosters are split into parts, linked from the output file
+#[test]
+fn paginate() {
+    let directory = std::env::temp_dir().join(format!("indico-transpose-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let index = directory.join("index.md");
+    run(&[
+        "three-modules.csv",
+        "--paginate",
+        "2",
+        "--output",
+        index.to_str().unwrap(),
+    ]);
+    let read = |name: &str| std::fs::read_to_string(directory.join(name)).unwrap();
+    assert_eq!(
+        read("index.md"),
+        "# Registrations to each module\n\n\
+         - [Part 1](part-001.md): Python basics 03/15, 09h00; Rust intro 03/15, 14h00\n\
+         - [Part 2](part-002.md): Deep Learning 03/16, 09:30\n"
+    );
+    assert!(read("part-001.md").contains("## Rust intro 03/15, 14h00\n"));
+    assert!(!read("part-001.md").contains("Deep Learning"));
+    assert!(read("part-002.md").contains("## Deep Learning 03/16, 09:30\n"));
+    assert!(!directory.join("part-003.md").exists());
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+/// Duplicate and widely spaced record IDs are reported
+#[test]
+fn check_ids() {
+    let (stdout, _) = run(&["duplicate-ids.csv", "--check-ids"]);
+    assert!(stdout.ends_with(
+        "# ID anomalies\n\n- ID 2 is shared by 2 records\n- No record between IDs 2 and 40\n"
+    ));
+    let (stdout, _) = run(&["registrations.csv", "--check-ids"]);
+    assert!(!stdout.contains("# ID anomalies"));
+}
+
+/// Displayed times follow the requested format
+#[test]
+fn time_format() {
+    let (stdout, _) = run(&[
+        "registrations.csv",
+        "--explain",
+        "bob@example.org",
+        "--time-format",
+        "[day]/[month] [hour]:[minute]",
+    ]);
+    assert!(stdout.contains("registered on 30/01 10:00 with priority 0"));
+}
+
+/// Unscheduled modules are an error if all modules must be scheduled
+#[test]
+fn require_all_scheduled() {
+    let output = Command::new(env!("CARGO_BIN_EXE_indico-transpose"))
+        .args(["registrations.csv", "--require-all-scheduled"])
+        .current_dir(FIXTURES)
+        .output()
+        .expect("Failed to run indico-transpose");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("no known start time"), "{stderr}");
+    assert!(stderr.contains("Mystery module"), "{stderr}");
+
+    run(&["three-modules.csv", "--require-all-scheduled"]);
+}
+
+/// The JSON export counts the registrants of each module, and overall totals
+#[test]
+fn json_counts() {
+    let path = std::env::temp_dir().join(format!(
+        "indico-transpose-{}-rosters.json",
+        std::process::id()
+    ));
+    run(&[
+        "registrations.csv",
+        "--capacities",
+        "capacities.csv",
+        "--json-out",
+        path.to_str().unwrap(),
+    ]);
+    let json = serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(&path).unwrap())
+        .expect("The export should be valid JSON");
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(json["total_persons"], 5);
+    assert_eq!(json["total_modules"], 4);
+    let modules = json["modules"].as_array().unwrap();
+    assert_eq!(modules.len(), 4);
+    for module in modules {
+        let registrants = module["registrants"].as_array().unwrap();
+        assert_eq!(module["count"], registrants.len(), "{module}");
+    }
+}
+
+/// Unscheduled modules follow the order of the canonical module list
+#[test]
+fn canonical_module_order() {
+    let headings = |stdout: String| {
+        stdout
+            .lines()
+            .filter_map(|line| line.strip_prefix("## "))
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    };
+    let (stdout, _) = run(&["unscheduled.csv", "--modules", "modules.txt"]);
+    assert_eq!(
+        headings(stdout),
+        [
+            "Zeta workshop",
+            "Python basics 03/15, 09h00",
+            "Alpha session",
+            "Deep Learning 03/16, 09:30",
+            "Mystery module"
+        ]
+    );
+    let (stdout, _) = run(&[
+        "unscheduled.csv",
+        "--modules",
+        "modules.txt",
+        "--unscheduled-placement",
+        "last",
+    ]);
+    assert_eq!(
+        headings(stdout),
+        [
+            "Python basics 03/15, 09h00",
+            "Deep Learning 03/16, 09:30",
+            "Zeta workshop",
+            "Alpha session",
+            "Mystery module"
+        ]
+    );
+}
+
+/// Task list rosters let people be ticked off, in roster order
+#[test]
+fn gfm_tasklist() {
+    let (stdout, _) = run(&["registrations.csv", "--format", "gfm-tasklist"]);
+    assert!(stdout.contains(
+        "## Deep Learning 03/16, 09:30\n\n\
+         - [ ] `David Leroy <david@example.org>` from IJCLab\n\
+         - [ ] `Bob Martin <bob@example.org>` from LAL\n\
+         - [ ] `Alice Durand <alice@example.org>` from IJCLab\n"
+    ));
+    assert!(!stdout.contains("1. "));
+}
+
+/// Hashed emails are consistent across the output, and hide the addresses
+#[test]
+fn hash_emails() {
+    let (stdout, _) = run(&[
+        "registrations.csv",
+        "--hash-emails",
+        "--email-salt",
+        "pepper",
+    ]);
+    assert!(!stdout.contains("@example.org"), "{stdout}");
+    let alice = stdout
+        .lines()
+        .filter(|line| line.contains("Alice Durand"))
+        .collect::<Vec<_>>();
+    assert_eq!(alice.len(), 2);
+    assert_eq!(alice[0][3..], alice[1][3..]);
+    let hash = indico_transpose::input::hash_email("pepper", "Alice@Example.org");
+    assert_eq!(hash.len(), 8);
+    assert!(alice[0].contains(&format!("<{hash}>")), "{}", alice[0]);
+    assert_ne!(
+        hash,
+        indico_transpose::input::hash_email("salt", "alice@example.org")
+    );
+}
+
+/// Data quality warnings can be reported as GitHub Actions annotations
+#[test]
+fn github_annotations() {
+    let (_, stderr) = run(&["registrations.csv", "--github-annotations"]);
+    assert!(stderr.lines().any(|line| line
+        == "::warning title=unparsed_module_time::Couldn't parse start time of module \
+            \"Mystery module\", it will be unordered in output"));
+}
+
+/// People whose modules are all filtered out are reported, so that they don't
+/// vanish from the output unnoticed
+#[test]
+fn report_hidden() {
+    let (stdout, _) = run(&[
+        "registrations.csv",
+        "--only-modules",
+        "Rust|Python",
+        "--report-hidden",
+    ]);
+    assert!(!stdout.contains("## Deep Learning"));
+    let report = stdout
+        .split("# Registrants without any displayed module")
+        .nth(1)
+        .expect("Hidden registrants should be reported");
+    assert!(report.contains("Bob Martin"));
+    for shown in ["Alice Durand", "Carol Petit", "David Leroy", "Eve Moreau"] {
+        assert!(!report.contains(shown), "{shown} should not be hidden");
+    }
+}
+
+/// Capacities set on the command line override the capacities file
+#[test]
+fn capacity_override() {
+    let (stdout, _) = run(&[
+        "registrations.csv",
+        "--capacities",
+        "capacities.csv",
+        "--capacity",
+        "Deep Learning 03/16, 09:30=2",
+    ]);
+    assert!(stdout.contains(
+        "## Deep Learning 03/16, 09:30\n\n\
+         1. `David Leroy <david@example.org>` from IJCLab\n\
+         2. `Bob Martin <bob@example.org>` from LAL\n\n\
+         ### Waiting list\n\n\
+         1. `Alice Durand <alice@example.org>` from IJCLab\n"
+    ));
+}
+
+/// The run report combines counts, module sizes, affiliations and warnings
+#[test]
+fn report_json() {
+    let path = std::env::temp_dir().join(format!(
+        "indico-transpose-{}-report.json",
+        std::process::id()
+    ));
+    run(&["registrations.csv", "--report-json", path.to_str().unwrap()]);
+    let json = serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(&path).unwrap())
+        .expect("The report should be valid JSON");
+    std::fs::remove_file(&path).unwrap();
+    for key in ["counts", "modules", "affiliations", "warnings"] {
+        assert!(json.get(key).is_some(), "Missing key {key} in {json}");
+    }
+    assert_eq!(json["counts"]["persons"], 5);
+    assert_eq!(json["modules"].as_array().unwrap().len(), 4);
+    assert_eq!(json["affiliations"][0]["affiliation"], "IJCLab");
+    assert_eq!(json["affiliations"][0]["count"], 2);
+    assert_eq!(json["warnings"]["unparsed_module_time"], 1);
+}
+
+/// Module capacities in the run report are the seat counts enforced during
+/// admission, which leave out cancelled people
+#[test]
+fn report_json_capacity() {
+    let path = temp_path("capacity-report.json");
+    run(&[
+        "states.csv",
+        "--state-aliases",
+        "state-aliases.csv",
+        "--capacity",
+        "Rust intro 15/03, 14h00=80%",
+        "--report-json",
+        path.to_str().unwrap(),
+    ]);
+    let json = serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(&path).unwrap())
+        .expect("The report should be valid JSON");
+    std::fs::remove_file(&path).unwrap();
+    let module = &json["modules"][0];
+    assert_eq!(module["registered"], 4);
+    assert_eq!(module["capacity"], 3);
+    assert_eq!(module["accepted"], 3);
+    assert_eq!(module["waitlisted"], 1);
+}
+
+/// Registrations that arrived since the previous run are reported on top of
+/// the full rosters
+#[test]
+fn since_last_run() {
+    let state = std::env::temp_dir().join(format!(
+        "indico-transpose-{}-last-run.txt",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&state);
+    let (first, _) = run(&[
+        "registrations-earlier.csv",
+        "--since-last-run",
+        state.to_str().unwrap(),
+    ]);
+    assert!(!first.contains("# New registrations"));
+    let (second, _) = run(&[
+        "registrations.csv",
+        "--since-last-run",
+        state.to_str().unwrap(),
+    ]);
+    std::fs::remove_file(&state).unwrap();
+    assert!(second.starts_with(
+        "# New registrations since 2024-02-02 08:15\n\n\
+         ## Rust intro 03/15, 14h00\n\n\
+         - `Carol Petit <carol@example.org>`\n\n\
+         ## Mystery module\n\n\
+         - `Carol Petit <carol@example.org>`\n\n"
+    ));
+    let full_rosters =
+        std::fs::read_to_string(Path::new(FIXTURES).join("registrations.md")).unwrap();
+    assert!(second.ends_with(&full_rosters));
+}
+
+/// VIPs are accepted ahead of earlier registrants, even in a full module
+#[test]
+fn vip() {
+    let (stdout, _) = run(&[
+        "registrations.csv",
+        "--capacity",
+        "Deep Learning 03/16, 09:30=1",
+        "--vip",
+        "vips.txt",
+    ]);
+    assert!(stdout.contains(
+        "## Deep Learning 03/16, 09:30\n\n\
+         1. `Alice Durand <alice@example.org>` from IJCLab (VIP)\n\n\
+         ### Waiting list\n\n\
+         1. `David Leroy <david@example.org>` from IJCLab\n\
+         2. `Bob Martin <bob@example.org>` from LAL\n"
+    ));
+}
+
+/// CSV columns that this program doesn't know about are reported
+#[test]
+fn extra_column_warning() {
+    let (stdout, stderr) = run(&["extra-column.csv"]);
+    assert!(stderr.contains(
+        "Ignoring 1 CSV column(s) that this program doesn't know about: \"Dietary requirements\""
+    ));
+    assert!(stdout.contains("1. `Alice Durand <alice@example.org>` from LAL"));
+
+    // Known columns are not reported
+    let (_, stderr) = run(&["registrations.csv"]);
+    assert!(!stderr.contains("doesn't know about"));
+}
+
+/// Statistics report how many duplicate registrations were merged
+#[test]
+fn duplicate_counts() {
+    let (stdout, _) = run(&["duplicates.csv", "--dedup", "--stats"]);
+    assert!(stdout.contains(
+        "# Statistics\n\n\
+         - 4 registration records\n\
+         - 2 unique persons\n\
+         - 2 duplicate registrations merged\n"
+    ));
+
+    // Without deduplication, every record is a person
+    let (stdout, _) = run(&["duplicates.csv", "--stats"]);
+    assert!(stdout.contains("- 4 registration records\n- 4 persons\n"));
+}
+
+/// Data quality warnings are saved as JSON, with their category
+#[test]
+fn warnings_json() {
+    let path = temp_path("warnings.json");
+    run(&["bad-data.csv", "--warnings-json", path.to_str().unwrap()]);
+    let json = serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(&path).unwrap())
+        .expect("Warnings should be valid JSON");
+    std::fs::remove_file(&path).unwrap();
+    let categories = json["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|warning| warning["category"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        categories,
+        [
+            "unparsed_module_time",
+            "invalid_email",
+            "empty_choice_of_modules",
+            "duplicate_email"
+        ]
+    );
+    assert_eq!(json["warnings"][0]["module"], "Mystery module");
+    assert_eq!(json["warnings"][1]["email"], "bob.example.org");
+}
+
+/// Rosters can be restricted to one affiliation, matched case-insensitively
+#[test]
+fn only_affiliation() {
+    let (stdout, _) = run(&["registrations.csv", "--only-affiliation", "ijclab"]);
+    assert!(stdout.contains("Alice Durand"));
+    assert!(stdout.contains("David Leroy"));
+    for other in ["Bob Martin", "Carol Petit", "Eve Moreau"] {
+        assert!(!stdout.contains(other), "{other} should be filtered out");
+    }
+}
+
+/// The order of CSV rows doesn't affect the output
+#[test]
+fn shuffled_rows() {
+    let args = ["--stats", "--by-affiliation", "--format", "markdown-table"];
+    let (expected, _) = run(&[&["registrations.csv"][..], &args].concat());
+    let (shuffled, _) = run(&[&["registrations-shuffled.csv"][..], &args].concat());
+    assert_eq!(shuffled, expected);
+}
+
+/// Attendance is marked in the rosters and summarized per module
+#[test]
+fn attendance_report() {
+    let (stdout, _) = run(&[
+        "registrations.csv",
+        "--attendance",
+        "attendance.txt",
+        "--attendance-report",
+    ]);
+    assert_snapshot(&stdout, "attendance.md");
+}
+
+/// The summary header follows the output format
+#[test]
+fn summary_header_format() {
+    let (stdout, _) = run(&["registrations.csv", "--summary-header", "--format", "html"]);
+    assert!(stdout.starts_with("<p>5 persons across 4 modules</p>\n<h1>"));
+    let (stdout, _) = run(&["three-modules.csv", "--summary-header", "--format", "plain"]);
+    assert!(
+        stdout.starts_with("2 persons across 3 modules\n\n"),
+        "{stdout}"
+    );
+}
+
+/// Warnings are mirrored to the warnings file
+#[test]
+fn warnings_file() {
+    let path = temp_path("warnings.log");
+    let (_, stderr) = run(&["bad-data.csv", "--warnings-file", path.to_str().unwrap()]);
+    let warnings = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(warnings.lines().count(), 4, "{warnings}");
+    for expected in [
+        "Couldn't parse start time of module \"Mystery module\"",
+        "Bob Martin has an invalid email address <bob.example.org>",
+        "Carol Petit <carol@example.org> did not choose any module",
+        "Several registrations use email address <alice@example.org>",
+    ] {
+        assert!(warnings.contains(expected), "Missing warning: {expected}");
+        assert!(stderr.contains(expected), "Missing warning: {expected}");
+    }
+}
+
+/// Regex rules of the affiliations file canonicalize affiliation spellings
+#[test]
+fn affiliation_regex() {
+    let (stdout, _) = run(&["spellings.csv", "--affiliations", "affiliations.txt"]);
+    assert_eq!(stdout.matches(" from IJCLab\n").count(), 3, "{stdout}");
+    assert!(stdout.contains("`David Leroy <david@example.org>` from CERN\n"));
+}
+
+/// Without a terminal to ask on, --interactive proceeds as if confirmed
+#[test]
+fn interactive_without_terminal() {
+    let args = [
+        "registrations.csv",
+        "--capacity",
+        "Deep Learning 03/16, 09:30=2",
+    ];
+    let (batch, _) = run(&args);
+    let (interactive, _) = run(&[&args[..], &["--interactive"]].concat());
+    let (confirmed, _) = run(&[&args[..], &["--interactive", "--yes"]].concat());
+    assert!(interactive.contains("### Waiting list"));
+    assert_eq!(interactive, batch);
+    assert_eq!(confirmed, batch);
+}
+
+/// Waitlisted people appear in the mail-merge CSV with their waiting list rank
+#[test]
+fn mailmerge_waitlist_rank() {
+    let path = temp_path("mailmerge.csv");
+    run(&[
+        "registrations.csv",
+        "--capacity",
+        "Deep Learning 03/16, 09:30=2",
+        "--mailmerge-csv",
+        path.to_str().unwrap(),
+    ]);
+    let csv = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(csv.starts_with("email,name,module,rank,status\n"));
+    assert!(csv.contains(
+        "david@example.org,David Leroy,\"Deep Learning 03/16, 09:30\",1,accepted\n\
+         bob@example.org,Bob Martin,\"Deep Learning 03/16, 09:30\",2,accepted\n\
+         alice@example.org,Alice Durand,\"Deep Learning 03/16, 09:30\",1,waitlist\n"
+    ));
+}
+
+/// Headings are translated with --locale fr, but data is left as is
+#[test]
+fn french_headings() {
+    let (stdout, _) = run(&[
+        "registrations.csv",
+        "--locale",
+        "fr",
+        "--capacity",
+        "Deep Learning 03/16, 09:30=2",
+        "--stats",
+    ]);
+    assert!(stdout.contains("# Inscriptions à chaque module\n"));
+    assert!(stdout.contains("### Liste d'attente\n"));
+    assert!(stdout.contains("# Statistiques\n"));
+    assert!(stdout.contains("## Deep Learning 03/16, 09:30\n"));
+    assert!(!stdout.contains("Registrations to each module"));
+}
+
+/// Only the modules whose registration count differs from the expected one
+/// are reported
+#[test]
+fn count_mismatches() {
+    let (stdout, _) = run(&[
+        "registrations.csv",
+        "--expected-counts",
+        "expected-counts.csv",
+    ]);
+    assert!(stdout.ends_with(
+        "# Count mismatches\n\n\
+         - Rust intro 03/15, 14h00: expected 4, found 3\n"
+    ));
+}
+
+/// Affiliations with a common parent are counted together when grouping
+/// statistics by parent
+#[test]
+fn affiliation_rollup() {
+    let (stdout, _) = run(&[
+        "registrations.csv",
+        "--stats",
+        "--stats-group-by",
+        "parent",
+        "--affiliation-hierarchy",
+        "hierarchy.csv",
+    ]);
+    assert!(stdout.contains(
+        "## Persons per affiliation\n\n\
+         - CNRS: 3\n\
+         - Université Paris-Saclay: 1\n\
+         - Unknown affiliation: 1\n"
+    ));
+}
+
+/// Grouping statistics by parent is meaningless without a hierarchy
+#[test]
+fn rollup_without_hierarchy() {
+    let output = Command::new(env!("CARGO_BIN_EXE_indico-transpose"))
+        .args(["registrations.csv", "--stats", "--stats-group-by", "parent"])
+        .current_dir(FIXTURES)
+        .output()
+        .expect("Failed to run indico-transpose");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--affiliation-hierarchy"));
+}
+
+/// Headings are shifted by --heading-offset, up to level 6
+#[test]
+fn heading_offset() {
+    let (stdout, _) = run(&["registrations.csv", "--heading-offset", "1"]);
+    assert!(stdout.starts_with(
+        "## Registrations to each module\n\n\
+         ### Python basics 03/15, 09h00\n"
+    ));
+    let (stdout, _) = run(&["registrations.csv", "--heading-offset", "5"]);
+    assert!(stdout.starts_with(
+        "###### Registrations to each module\n\n\
+         ###### Python basics 03/15, 09h00\n"
+    ));
+}
+
+/// The input hash is the SHA-256 of the input file, and is the same every run
+#[test]
+fn input_hash() {
+    let (first, _) = run(&["registrations.csv", "--input-hash"]);
+    let (second, _) = run(&["registrations.csv", "--input-hash"]);
+    assert!(first.ends_with(
+        "<!-- Input SHA-256: bf4a6d5977452c96aa087e9d13c1cf51d62c1fb135a83913bdf66bfe59121f07 -->\n"
+    ));
+    assert_eq!(first, second);
+}
+
+/// Module headings can show the parsed start time, or that it is unknown
+#[test]
+fn module_times_in_headings() {
+    let (stdout, _) = run(&["bad-data.csv", "--show-module-times"]);
+    assert!(stdout.contains("## Rust intro 15/03, 14h00 — 2024-03-15 14:00\n"));
+    assert!(stdout.contains("## Mystery module — (time unknown)\n"));
+    let (stdout, _) = run(&["bad-data.csv", "--show-module-times", "--year", "2025"]);
+    assert!(stdout.contains("## Rust intro 15/03, 14h00 — 2025-03-15 14:00\n"));
+}
+
+/// Affiliations that no rule simplified are reported with their count, unlike
+/// those that a rule simplified
+#[test]
+fn unsimplified_affiliations() {
+    let (stdout, _) = run(&["registrations.csv", "--report-unsimplified-affiliations"]);
+    assert!(stdout.ends_with(
+        "# Unsimplified affiliations\n\n\
+         - LAL: 1\n\
+         - Université Paris-Saclay: 1\n"
+    ));
+    assert!(!stdout.contains("IJCLAB - IN2P3 - CNRS"));
+}
+
+/// One email is rendered from the template for each (person, module)
+#[test]
+fn render_emails() {
+    let dir = temp_path("emails");
+    run(&[
+        "registrations.csv",
+        "--capacity",
+        "Deep Learning 03/16, 09:30=2",
+        "--email-template",
+        "email-template.txt",
+        "--render-emails",
+        dir.to_str().unwrap(),
+    ]);
+    let email =
+        std::fs::read_to_string(dir.join("alice-example-org--deep-learning-03-16-09-30.txt"));
+    let num_emails = std::fs::read_dir(&dir).unwrap().count();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        email.unwrap(),
+        "Dear Alice Durand,\n\n\
+         You are #1 (waitlist) for \"Deep Learning 03/16, 09:30\".\n\
+         We will write to alice@example.org again. {{unknown}}\n"
+    );
+    assert_eq!(num_emails, 9);
+}
+
+/// Registrations which would get the same email file name don't overwrite
+/// each other
+#[test]
+fn render_emails_collision() {
+    let dir = temp_path("colliding-emails");
+    run(&[
+        "duplicates.csv",
+        "--email-template",
+        "email-template.txt",
+        "--render-emails",
+        dir.to_str().unwrap(),
+    ]);
+    let mut file_names = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    std::fs::remove_dir_all(&dir).unwrap();
+    file_names.sort();
+    assert_eq!(
+        file_names,
+        [
+            "alice-example-org--deep-learning-16-03-09-30.txt",
+            "alice-example-org--rust-intro-15-03-14h00-2.txt",
+            "alice-example-org--rust-intro-15-03-14h00.txt",
+            "bob-example-org--rust-intro-15-03-14h00.txt",
+        ]
+    );
+}
+
+/// An input glob pattern merges all the files that it matches, and is an
+/// error if it matches nothing
+#[test]
+fn input_glob() {
+    let dir = temp_path("exports");
+    std::fs::create_dir_all(&dir).unwrap();
+    for (fixture, copy) in [("registrations.csv", "a.csv"), ("bad-data.csv", "b.csv")] {
+        std::fs::copy(Path::new(FIXTURES).join(fixture), dir.join(copy)).unwrap();
+    }
+    std::fs::write(dir.join("notes.txt"), "Not a CSV file").unwrap();
+    let pattern = dir.join("*.csv");
+    let (stdout, _) = run(&[pattern.to_str().unwrap(), "--stats"]);
+    let missing = Command::new(env!("CARGO_BIN_EXE_indico-transpose"))
+        .arg(dir.join("*.tsv"))
+        .output()
+        .expect("Failed to run indico-transpose");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(stdout.contains("- 9 registration records\n"));
+    assert!(!missing.status.success());
+    let stderr = String::from_utf8_lossy(&missing.stderr);
+    assert!(stderr.contains("No input file matches"), "{stderr}");
+}
+
+/// Unknown CSV columns are only reported once when several inputs have them
+#[test]
+fn input_glob_extra_column_warning() {
+    let dir = temp_path("extra-column-exports");
+    std::fs::create_dir_all(&dir).unwrap();
+    for copy in ["a.csv", "b.csv"] {
+        std::fs::copy(Path::new(FIXTURES).join("extra-column.csv"), dir.join(copy)).unwrap();
+    }
+    let (_, stderr) = run(&[dir.join("*.csv").to_str().unwrap()]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(stderr.matches("doesn't know about").count(), 1, "{stderr}");
+}
+
+/// Module metadata is displayed under the heading of the modules that have it
+#[test]
+fn module_metadata() {
+    let (stdout, _) = run(&["registrations.csv", "--module-metadata", "metadata.toml"]);
+    assert!(stdout.contains(
+        "## Deep Learning 03/16, 09:30\n\n\
+         Neural networks from scratch\n\n\
+         Location: Room 101\n\n\
+         1. "
+    ));
+    assert!(stdout.contains("## Rust intro 03/15, 14h00\n\n1. "));
+}
+
+/// Conflicting metadata for one module is a data warning
+#[test]
+fn conflicting_module_metadata() {
+    let path = temp_path("metadata-warnings.json");
+    let (_, stderr) = run(&[
+        "registrations.csv",
+        "--module-metadata",
+        "conflicting-metadata.toml",
+        "--warnings-json",
+        path.to_str().unwrap(),
+    ]);
+    let json = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(stderr.contains(
+        "Module \"Deep Learning 03/16, 09:30\" has conflicting entries in the metadata file"
+    ));
+    assert!(json.contains(
+        "\"category\": \"conflicting_module_metadata\",\n      \
+         \"module\": \"Deep Learning 03/16, 09:30\""
+    ));
+}
+
+/// Registration times without an offset use the one from --assume-offset
+#[test]
+fn assume_offset() {
+    // 09:30 is after 08:00 UTC, but 09:30+02:00 is before
+    let (utc, _) = run(&["naive-times.csv"]);
+    assert!(utc.contains("1. `Bob Martin <bob@example.org>` from LAL\n2. `Alice Durand"));
+    let (shifted, _) = run(&["naive-times.csv", "--assume-offset", "+02:00"]);
+    assert!(shifted.contains("1. `Alice Durand <alice@example.org>` from LAL\n2. `Bob Martin"));
+}
+
+/// Snapshots that differ by one person are compared module by module
+#[test]
+fn diff_snapshots() {
+    let (stdout, _) = run(&["diff", "registrations-earlier.csv", "registrations.csv"]);
+    assert_eq!(
+        stdout,
+        "# Registration changes\n\n\
+         ## Rust intro 03/15, 14h00\n\n\
+         1 added, 0 removed, net change +1\n\n\
+         - Added: carol@example.org\n\n\
+         ## Mystery module\n\n\
+         1 added, 0 removed, net change +1\n\n\
+         - Added: carol@example.org\n"
+    );
+    let (stdout, _) = run(&["diff", "registrations.csv", "registrations-earlier.csv"]);
+    assert!(stdout.contains(
+        "## Rust intro 03/15, 14h00\n\n\
+         0 added, 1 removed, net change -1\n\n\
+         - Removed: carol@example.org\n"
+    ));
+}
+
+/// The admission of a waitlisted person is explained module by module
+#[test]
+fn explain_waitlisted() {
+    let (stdout, _) = run(&[
+        "registrations.csv",
+        "--capacity",
+        "Deep Learning 03/16, 09:30=2",
+        "--explain",
+        "alice@example.org",
+    ]);
+    assert_eq!(
+        stdout,
+        "# Admission explanation\n\n\
+         `Alice Durand <alice@example.org>` from IJCLab registered on 2024-02-01 10:00 with priority 0, and chose 2 modules.\n\n\
+         1. Rust intro 03/15, 14h00: registration rank 1 of 3, no capacity limit, accepted (seat 1)\n\
+         2. Deep Learning 03/16, 09:30: registration rank 3 of 3, capacity 2 (2 seats), waitlisted at position 1 (module full)\n"
+    );
+}
+
+/// Same as [`markdown_rosters()`], with two preamble lines before the header
+#[test]
+fn skipped_preamble() {
+    let (stdout, _) = run(&["preamble.csv", "--skip-lines", "2"]);
+    assert_snapshot(&stdout, "registrations.md");
+}
+
+/// With --preserve-input-order, modules and people are listed in CSV order
+#[test]
+fn preserve_input_order() {
+    let (stdout, _) = run(&["registrations.csv", "--preserve-input-order"]);
+    assert!(stdout.starts_with(
+        "# Registrations to each module\n\n\
+         ## Rust intro 03/15, 14h00\n\n\
+         1. `Alice Durand <alice@example.org>` from IJCLab\n\
+         2. `Carol Petit <carol@example.org>`\n\
+         3. `Eve Moreau <eve@example.org>` from Université Paris-Saclay\n\n\
+         ## Deep Learning 03/16, 09:30\n\n\
+         1. `Alice Durand <alice@example.org>` from IJCLab\n\
+         2. `Bob Martin <bob@example.org>` from LAL\n\
+         3. `David Leroy <david@example.org>` from IJCLab\n\n\
+         ## Mystery module\n\n"
+    ));
+    assert!(stdout.ends_with(
+        "## Python basics 03/15, 09h00\n\n\
+         1. `David Leroy <david@example.org>` from IJCLab\n\
+         2. `Eve Moreau <eve@example.org>` from Université Paris-Saclay\n"
+    ));
+}
+
+/// Modules are grouped by track prefix, and modules without one come last
+#[test]
+fn tracks() {
+    let (stdout, _) = run(&["tracks.csv", "--group-by-prefix"]);
+    assert_eq!(
+        stdout,
+        "# Registrations to each module\n\n\
+         ## ML\n\n\
+         ### Intro 15/03, 09h00\n\n\
+         1. `Alice Durand <alice@example.org>` from LAL\n\n\
+         ### Advanced 16/03, 09h00\n\n\
+         1. `Alice Durand <alice@example.org>` from LAL\n\n\
+         ## Ungrouped\n\n\
+         ### Rust intro 15/03, 14h00\n\n\
+         1. `Alice Durand <alice@example.org>` from LAL\n"
+    );
+}
+
+/// Module statistics count distinct simplified affiliations, ignoring unknown
+/// ones
+#[test]
+fn affiliation_diversity() {
+    let (stdout, _) = run(&["registrations.csv", "--stats"]);
+    assert!(stdout.ends_with(
+        "## Affiliations per module\n\n\
+         - Python basics 03/15, 09h00: 2 registrants from 2 affiliations\n\
+         - Rust intro 03/15, 14h00: 3 registrants from 2 affiliations\n\
+         - Deep Learning 03/16, 09:30: 3 registrants from 2 affiliations\n\
+         - Mystery module: 1 registrant from 0 affiliations\n"
+    ));
+}
+
+/// Compact rosters fit on one line per module, in registration order
+#[test]
+fn compact_rosters() {
+    let (stdout, _) = run(&["registrations.csv", "--compact"]);
+    assert!(stdout
+        .contains("- Deep Learning 03/16, 09:30 (3): David Leroy, Bob Martin, Alice Durand\n"));
+    assert_eq!(stdout.lines().count(), 6);
+}
+
+/// Affiliations are renamed by the normalizer command, unless it fails
+#[cfg(unix)]
+#[test]
+fn affiliation_normalizer() {
+    let (stdout, _) = run(&[
+        "three-modules.csv",
+        "--affiliation-normalizer",
+        "tr A-Z a-z",
+    ]);
+    assert!(stdout.contains("1. `Alice Durand <alice@example.org>` from lal\n"));
+    let (stdout, stderr) = run(&["three-modules.csv", "--affiliation-normalizer", "exit 3"]);
+    assert!(stdout.contains("1. `Alice Durand <alice@example.org>` from LAL\n"));
+    assert!(
+        stderr.contains("Affiliation normalizer \"exit 3\" failed"),
+        "{stderr}"
+    );
+}
+
+/// Attendance sheets have a blank signature column
+#[test]
+fn attendance_sheet() {
+    let (stdout, _) = run(&["registrations.csv", "--format", "attendance-sheet"]);
+    assert!(stdout.contains(
+        "## Python basics 03/15, 09h00\n\n\
+         | Rank | Name | Affiliation | Signature |\n\
+         |-----:|------|-------------|------------------------|\n\
+         | 1 | David Leroy | IJCLab |                         |\n"
+    ));
+    assert!(!stdout.contains("@example.org"));
+}
+
+/// People who chose several modules, or who registered twice with the same
+/// email, are only counted once as distinct persons
+#[test]
+fn distinct_persons() {
+    let (stdout, _) = run(&["bad-data.csv", "--stats"]);
+    assert!(stdout.contains("- 4 module registrations, from 2 distinct persons\n"));
+}
+
+/// Options can come from a config file, and command-line arguments override
+/// both the config keys that they set and those that they conflict with
+#[test]
+fn config_file() {
+    let (stdout, _) = run(&["--config", "config.toml"]);
+    assert!(stdout.contains("<h1>Registrations to each module</h1>"));
+    assert!(stdout.contains("<li><code>Alice Durand</code></li>"));
+    assert!(stdout
+        .contains("<li>Deep Learning 03/16, 09:30: 3 registered, capacity 2, 1 waitlisted</li>"));
+
+    // --no-affiliation conflicts with the identity format of the config file
+    let (stdout, _) = run(&[
+        "--config",
+        "config.toml",
+        "--format",
+        "plain",
+        "--no-affiliation",
+    ]);
+    assert!(stdout.contains("Registrations to each module\n============================\n"));
+    assert!(stdout.contains("1. Alice Durand <alice@example.org>\n"));
+}
+
+/// The capacity summary only lists oversubscribed modules
+#[test]
+fn capacity_summary() {
+    let (stdout, _) = run(&[
+        "registrations.csv",
+        "--capacities",
+        "capacities.csv",
+        "--capacity",
+        "Deep Learning 03/16, 09:30=2",
+    ]);
+    assert!(stdout.starts_with(
+        "# Capacity summary\n\n\
+         - Deep Learning 03/16, 09:30: 3 registered, capacity 2, 1 waitlisted\n\n\
+         # Registrations to each module\n"
+    ));
+
+    // Nothing is written if every module is within capacity
+    let (stdout, _) = run(&["registrations.csv", "--capacities", "capacities.csv"]);
+    assert!(!stdout.contains("# Capacity summary"));
+}
+
+/// Date-only registration times are midnight of that day, if allowed
+#[test]
+fn date_only_registration() {
+    let (stdout, _) = run(&[
+        "date-only.csv",
+        "--allow-date-only",
+        "--explain",
+        "alice@example.org",
+    ]);
+    assert!(stdout.contains("registered on 2024-03-15 00:00 with priority 0"));
+    let output = Command::new(env!("CARGO_BIN_EXE_indico-transpose"))
+        .arg("date-only.csv")
+        .current_dir(FIXTURES)
+        .output()
+        .expect("Failed to run indico-transpose");
+    assert!(!output.status.success());
+}
+
+/// People registered to any of two modules are listed once, with the modules
+/// that they chose among them
+#[test]
+fn registered_to_any() {
+    let (stdout, _) = run(&[
+        "registrations.csv",
+        "--registered-to-any",
+        "Python basics 03/15, 09h00;Rust intro 03/15, 14h00",
+    ]);
+    assert_eq!(
+        stdout,
+        "# Persons registered to any of these modules\n\n\
+         - Python basics 03/15, 09h00\n\
+         - Rust intro 03/15, 14h00\n\n\
+         4 persons:\n\n\
+         1. `Alice Durand <alice@example.org>` from IJCLab: Rust intro 03/15, 14h00\n\
+         2. `Carol Petit <carol@example.org>`: Rust intro 03/15, 14h00\n\
+         3. `David Leroy <david@example.org>` from IJCLab: Python basics 03/15, 09h00\n\
+         4. `Eve Moreau <eve@example.org>` from Université Paris-Saclay: \
+         Python basics 03/15, 09h00; Rust intro 03/15, 14h00\n"
+    );
+}
+
+/// The vCard export has one card per distinct email address
+#[test]
+fn vcards() {
+    let path = temp_path("contacts.vcf");
+    run(&["bad-data.csv", "--vcard", path.to_str().unwrap()]);
+    let vcards = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(vcards.matches("BEGIN:VCARD\r\n").count(), 3);
+    assert_eq!(vcards.matches("END:VCARD\r\n").count(), 3);
+    let emails = vcards
+        .lines()
+        .filter_map(|line| line.strip_prefix("EMAIL:"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        emails,
+        ["alice@example.org", "bob.example.org", "carol@example.org"]
+    );
+    assert!(vcards.starts_with(
+        "BEGIN:VCARD\r\nVERSION:4.0\r\nFN:Alice Durand\r\nEMAIL:alice@example.org\r\nORG:LAL\r\nEND:VCARD\r\n"
+    ));
+}
+
+/// Registration states without an alias are reported once with their count,
+/// and treated as active
+#[test]
+fn unrecognized_states() {
+    let (stdout, stderr) = run(&["states.csv", "--state-aliases", "state-aliases.csv"]);
+    assert!(stderr.contains(
+        "Some registration states have no alias, these people are treated as active: \
+         \"Complete\" (2), \"On hold\" (1)"
+    ));
+    assert_eq!(stderr.matches("have no alias").count(), 1);
+    assert!(stdout.contains("4. `Eve Moreau <eve@example.org>` from LAL\n"));
+    assert!(!stdout.contains("Carol Petit"));
+
+    // No warning is emitted without state aliases
+    let (_, stderr) = run(&["states.csv"]);
+    assert!(!stderr.contains("have no alias"));
+}
+
+/// Statistics, table headers and waiting list markers follow --locale
+#[test]
+fn french_details() {
+    let (stdout, _) = run(&["registrations.csv", "--locale", "fr", "--stats"]);
+    assert!(stdout.contains(
+        "- 5 fiches d'inscription\n\
+         - 5 personnes\n\
+         - 4 modules\n\
+         - 9 inscriptions à des modules, de 5 personnes distinctes\n"
+    ));
+    assert!(stdout.contains("- Mystery module: 1 inscrit de 0 affiliation\n"));
+    let (stdout, _) = run(&[
+        "registrations.csv",
+        "--locale",
+        "fr",
+        "--format",
+        "attendance-sheet",
+    ]);
+    assert!(stdout.contains("| Rang | Nom | Affiliation | Signature |\n"));
+    let (stdout, _) = run(&[
+        "registrations.csv",
+        "--locale",
+        "fr",
+        "--by-person",
+        "--capacity",
+        "Deep Learning 03/16, 09:30=2",
+    ]);
+    assert!(stdout.contains("- Deep Learning 03/16, 09:30 (liste d'attente)\n"));
+    let (stdout, _) = run(&[
+        "--locale",
+        "fr",
+        "diff",
+        "registrations-earlier.csv",
+        "registrations.csv",
+    ]);
+    assert!(stdout.contains(
+        "1 ajouté, 0 retiré, variation nette +1\n\n\
+         - Ajout: carol@example.org\n"
+    ));
+    let (stdout, _) = run(&[
+        "--locale",
+        "fr",
+        "diff",
+        "registrations.csv",
+        "duplicates.csv",
+    ]);
+    assert!(stdout.contains("2 ajoutés, 0 retiré, variation nette +2\n"));
+}